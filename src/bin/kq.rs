@@ -0,0 +1,188 @@
+use kdl::{KdlDocument, KdlError, KdlValue};
+use query_kdl::{
+    parser::Query,
+    resolve::{to_document, Match},
+};
+use std::{
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: kq [OPTIONS] <QUERY> [FILE]...
+
+Query KDL documents and print the matching nodes as a KDL document.
+Entries selected with @ are printed as KDL, one per line, and values
+selected with $ are printed raw, one per line.
+With no FILE, or when FILE is -, read standard input.
+
+Options:
+  -h, --help     Print this help
+  -V, --version  Print the version
+
+Exit status:
+  0  at least one node matched
+  1  no node matched
+  2  invalid command line or query
+  3  an input could not be read or the output could not be written
+  4  an input is not a valid KDL document";
+
+/// Exit statuses of `kq`, ordered by priority when several inputs fail.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Found = 0,
+    NotFound = 1,
+    Usage = 2,
+    Io = 3,
+    Kdl = 4,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+struct Args {
+    query: String,
+    files: Vec<String>,
+}
+
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut positionals = Vec::new();
+    let mut options_ended = false;
+    for arg in std::env::args().skip(1) {
+        if options_ended || arg == "-" || !arg.starts_with('-') {
+            positionals.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--" => options_ended = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(None);
+            }
+            "-V" | "--version" => {
+                println!("kq {}", env!("CARGO_PKG_VERSION"));
+                return Ok(None);
+            }
+            _ => return Err(format!("unknown option: {arg}")),
+        }
+    }
+    let mut positionals = positionals.into_iter();
+    let Some(query) = positionals.next() else {
+        return Err("missing query".to_string());
+    };
+    let mut files: Vec<_> = positionals.collect();
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+    Ok(Some(Args { query, files }))
+}
+
+fn read_input(file: &str) -> io::Result<String> {
+    let mut content = String::new();
+    if file == "-" {
+        io::stdin().read_to_string(&mut content)?;
+    } else {
+        content = std::fs::read_to_string(file)?;
+    }
+    Ok(content)
+}
+
+/// Appends the matches to the output, consecutive nodes forming a KDL document
+fn push_matches(output: &mut String, matches: Vec<Match>) {
+    let mut nodes = Vec::new();
+    for found in matches {
+        let line = match found {
+            Match::Node(node) => {
+                nodes.push(node);
+                continue;
+            }
+            Match::Entry(entry) => entry.to_string().trim().to_string(),
+            Match::Value(KdlValue::String(s)) => s.clone(),
+            Match::Value(value) => value.to_string(),
+        };
+        if !nodes.is_empty() {
+            output.push_str(&to_document(nodes.drain(..)).to_string());
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    if !nodes.is_empty() {
+        output.push_str(&to_document(nodes).to_string());
+    }
+}
+
+fn report_kdl_error(name: &str, error: &KdlError) {
+    if error.diagnostics.is_empty() {
+        eprintln!("kq: {name}: {error}");
+    }
+    for diagnostic in &error.diagnostics {
+        let before = &error.input[..diagnostic.span.offset().min(error.input.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        eprintln!("kq: {name}:{line}:{column}: {diagnostic}");
+        if let Some(help) = &diagnostic.help {
+            eprintln!("  help: {help}");
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => return Status::Found.into(),
+        Err(e) => {
+            eprintln!("kq: {e}\n\n{USAGE}");
+            return Status::Usage.into();
+        }
+    };
+    let query = match Query::parse(&args.query) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("{}", e.report(&args.query));
+            return Status::Usage.into();
+        }
+    };
+
+    let mut output = String::new();
+    let mut failure = None;
+    for file in &args.files {
+        let name = if file == "-" { "<stdin>" } else { file };
+        let content = match read_input(file) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("kq: {name}: {e}");
+                failure = failure.max(Some(Status::Io));
+                continue;
+            }
+        };
+        let kdoc = match content.parse::<KdlDocument>() {
+            Ok(kdoc) => kdoc,
+            Err(e) => {
+                report_kdl_error(name, &e);
+                failure = failure.max(Some(Status::Kdl));
+                continue;
+            }
+        };
+        push_matches(&mut output, query.clone().resolve_matches(&kdoc));
+    }
+
+    if !output.is_empty() {
+        let mut stdout = io::stdout().lock();
+        match write!(stdout, "{output}").and_then(|_| stdout.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            Err(e) => {
+                eprintln!("kq: <stdout>: {e}");
+                failure = failure.max(Some(Status::Io));
+            }
+            Ok(()) => (),
+        }
+    }
+    match failure {
+        Some(status) => status.into(),
+        None if output.is_empty() => Status::NotFound.into(),
+        None => Status::Found.into(),
+    }
+}
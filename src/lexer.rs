@@ -1,7 +1,16 @@
-#[derive(Debug, PartialEq, Eq)]
+/// Byte range of a token in the lexed input
+pub type Span = std::ops::Range<usize>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TokenType<'a> {
     String(&'a str),
     Alphanumeric(&'a str),
+    /// Alphanumeric characters mixed with `*` or `?` wildcards
+    Glob(&'a str),
+    /// `~/pattern/`, the closing slash may be missing
+    Regex(&'a str),
+    /// `<name>::`, only the name is kept
+    Axis(&'a str),
     Slash,
     DoubleSlash,
     Point,
@@ -12,8 +21,23 @@ pub enum TokenType<'a> {
     LeaveSquareBracket,
     EnterCurlyBracket,
     LeaveCurlyBracket,
+    EnterParenthesis,
+    LeaveParenthesis,
     Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    CaretEqual,
+    DollarEqual,
+    StarEqual,
+    TildeEqual,
     Pipe,
+    Semicolon,
+    Bang,
+    At,
+    Dollar,
     Hash,
     Unknown(&'a str),
 }
@@ -23,6 +47,9 @@ impl<'a> std::fmt::Display for TokenType<'a> {
         match self {
             TokenType::String(v) => write!(f, "{}", v),
             TokenType::Alphanumeric(v) => write!(f, "{}", v),
+            TokenType::Glob(v) => write!(f, "{}", v),
+            TokenType::Regex(v) => write!(f, "{}", v),
+            TokenType::Axis(v) => write!(f, "{}::", v),
             TokenType::Slash => write!(f, "/"),
             TokenType::DoubleSlash => write!(f, "//"),
             TokenType::Point => write!(f, "."),
@@ -33,22 +60,47 @@ impl<'a> std::fmt::Display for TokenType<'a> {
             TokenType::LeaveSquareBracket => write!(f, "]"),
             TokenType::EnterCurlyBracket => write!(f, "{{"),
             TokenType::LeaveCurlyBracket => write!(f, "}}"),
+            TokenType::EnterParenthesis => write!(f, "("),
+            TokenType::LeaveParenthesis => write!(f, ")"),
             TokenType::Equal => write!(f, "="),
+            TokenType::NotEqual => write!(f, "!="),
+            TokenType::Less => write!(f, "<"),
+            TokenType::LessEqual => write!(f, "<="),
+            TokenType::Greater => write!(f, ">"),
+            TokenType::GreaterEqual => write!(f, ">="),
+            TokenType::CaretEqual => write!(f, "^="),
+            TokenType::DollarEqual => write!(f, "$="),
+            TokenType::StarEqual => write!(f, "*="),
+            TokenType::TildeEqual => write!(f, "~="),
             TokenType::Pipe => write!(f, "|"),
+            TokenType::Semicolon => write!(f, ";"),
+            TokenType::Bang => write!(f, "!"),
+            TokenType::At => write!(f, "@"),
+            TokenType::Dollar => write!(f, "$"),
             TokenType::Hash => write!(f, "#"),
             TokenType::Unknown(v) => write!(f, "<unknown: {}>", v),
         }
     }
 }
 
+/// A token with its location in the lexed input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub token: TokenType<'a>,
+    pub span: Span,
+}
+
+#[derive(Clone)]
 pub struct Lexer<'a> {
     input: &'a str,
+    offset: usize,
 }
 
 impl<'a, T: AsRef<str> + ?Sized> From<&'a T> for Lexer<'a> {
     fn from(value: &'a T) -> Self {
         Self {
             input: value.as_ref(),
+            offset: 0,
         }
     }
 }
@@ -56,6 +108,12 @@ impl<'a, T: AsRef<str> + ?Sized> From<&'a T> for Lexer<'a> {
 impl<'a> Iterator for Lexer<'a> {
     type Item = TokenType<'a>;
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().map(|token| token.token)
+    }
+}
+impl<'a> Lexer<'a> {
+    /// Returns the next token along with its span
+    pub fn next_token(&mut self) -> Option<Token<'a>> {
         // Skip whitespaces
         let byte_offset = self
             .input
@@ -63,47 +121,111 @@ impl<'a> Iterator for Lexer<'a> {
             .take_while(|c| c.is_whitespace())
             .map(|c| c.len_utf8())
             .sum();
+        self.advance(byte_offset);
 
-        self.input = &self.input[byte_offset..];
+        let start = self.offset;
         let c_token = self.input.chars().next();
-        match c_token {
+        let token = match c_token {
             None => None,
             Some('"' | '\'') => self.get_text(),
-            Some(c) if c.is_alphanumeric() || c == '_' => self.get_alphanumeric(),
+            // Raw texts like `#"C:\path"#`, other `#` start a keyword
+            Some('#') if self.input.trim_start_matches('#').starts_with('"') => self.get_text(),
+            Some(c) if c.is_ascii_lowercase() && self.axis_len().is_some() => self.get_axis(),
+            Some(c) if is_word_char(c) || c == '?' => self.get_alphanumeric(),
+            Some('*') if self.input[1..].starts_with(|c| is_word_char(c) || c == '?') => {
+                self.get_alphanumeric()
+            }
+            Some('~') if self.input[1..].starts_with('/') => self.get_regex(),
             Some(_) => self.get_token(),
-        }
+        }?;
+        Some(Token {
+            token,
+            span: start..self.offset,
+        })
     }
-}
-impl<'a> Lexer<'a> {
+    /// Iterates over the tokens along with their spans
+    pub fn spanned(mut self) -> impl Iterator<Item = Token<'a>> {
+        std::iter::from_fn(move || self.next_token())
+    }
+    /// Byte offset of the lexer in the input
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Reads a quoted text, `"..."`, `'...'` or `"""..."""`, or a raw text with the same number of
+    /// `#` around it and no escapes
     fn get_text(&mut self) -> Option<<Self as Iterator>::Item> {
-        let mut iter_chars = self.input.chars();
-        let c_str = iter_chars.next().unwrap();
+        let hashes = self.input.bytes().take_while(|b| *b == b'#').count();
+        let quoted = &self.input[hashes..];
+        let quote = quoted.chars().next()?;
+        let quotes = match quoted.starts_with("\"\"\"") {
+            true => 3,
+            false => 1,
+        };
+        let closing = quoted[..quotes].to_string() + &self.input[..hashes];
+        let contents = &quoted[quotes..];
         let mut escaped = false;
-        let mut len_str = c_str.len_utf8();
-        for c in iter_chars {
-            len_str += c.len_utf8();
+        let mut len = contents.len();
+        for (i, c) in contents.char_indices() {
             if escaped {
                 escaped = false;
-                continue;
+            } else if c == '\\' && hashes == 0 {
+                escaped = true;
+            } else if c == quote && contents[i..].starts_with(&closing) {
+                len = i + closing.len();
+                break;
             }
-            if c == '\\' {
+        }
+        self.advance_and_return(hashes + quotes + len)
+            .map(TokenType::String)
+    }
+    /// Length of the axis name if the input starts with `<name>::`
+    fn axis_len(&self) -> Option<usize> {
+        let len = self
+            .input
+            .bytes()
+            .take_while(|b| b.is_ascii_lowercase() || *b == b'-')
+            .count();
+        self.input[len..].starts_with("::").then_some(len)
+    }
+    fn get_axis(&mut self) -> Option<<Self as Iterator>::Item> {
+        let len = self.axis_len()?;
+        let name = self.advance_and_return(len)?;
+        self.advance(2);
+        Some(TokenType::Axis(name))
+    }
+    fn get_regex(&mut self) -> Option<<Self as Iterator>::Item> {
+        let mut escaped = false;
+        let mut len = 2;
+        for c in self.input[2..].chars() {
+            len += c.len_utf8();
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
                 escaped = true;
-            } else if c == c_str {
+            } else if c == '/' {
                 break;
             }
         }
-        self.advance_and_return(len_str).map(TokenType::String)
+        self.advance_and_return(len).map(TokenType::Regex)
     }
     fn get_alphanumeric(&mut self) -> Option<<Self as Iterator>::Item> {
-        let it = self
-            .input
-            .chars()
-            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
-            .map(|c| (c.len_utf8(), c));
+        let input = self.input;
+        let it = input
+            .char_indices()
+            .take_while(|(i, c)| {
+                is_word_char(*c)
+                    || matches!(c, '.' | '?' | '\'')
+                    // `name*=value` is a property containing a value, `name!=value` is different
+                    || (matches!(c, '*' | '!' | '^' | '$') && !input[i + 1..].starts_with('='))
+            })
+            .map(|(_, c)| (c.len_utf8(), c));
         let mut n_points = 0;
         let mut len = 0;
+        let mut glob = false;
         for (clen, c) in it {
             len += clen;
+            glob |= matches!(c, '*' | '?');
             if c != '.' {
                 n_points = 0;
                 continue;
@@ -114,7 +236,11 @@ impl<'a> Lexer<'a> {
                 break;
             }
         }
-        self.advance_and_return(len).map(TokenType::Alphanumeric)
+        let word = self.advance_and_return(len)?;
+        Some(match glob {
+            true => TokenType::Glob(word),
+            false => TokenType::Alphanumeric(word),
+        })
     }
     fn get_token(&mut self) -> Option<<Self as Iterator>::Item> {
         use TokenType::*;
@@ -132,6 +258,8 @@ impl<'a> Lexer<'a> {
             ']' => LeaveSquareBracket,
             '{' => EnterCurlyBracket,
             '}' => LeaveCurlyBracket,
+            '(' => EnterParenthesis,
+            ')' => LeaveParenthesis,
             '.' => match iter_chars.next() {
                 Some((l, '.')) => {
                     offset += l;
@@ -144,28 +272,127 @@ impl<'a> Lexer<'a> {
                     offset += l;
                     DoubleStar
                 }
+                Some((l, '=')) => {
+                    offset += l;
+                    StarEqual
+                }
                 Some(_) | None => Star,
             },
+            '^' | '$' | '~' if self.input[1..].starts_with('=') => {
+                offset += 1;
+                match c {
+                    '^' => CaretEqual,
+                    '$' => DollarEqual,
+                    _ => TildeEqual,
+                }
+            }
             '=' => Equal,
+            '<' | '>' | '!' => match (c, iter_chars.next()) {
+                (_, Some((l, '='))) => {
+                    offset += l;
+                    match c {
+                        '<' => LessEqual,
+                        '>' => GreaterEqual,
+                        _ => NotEqual,
+                    }
+                }
+                ('<', _) => Less,
+                ('>', _) => Greater,
+                _ => Bang,
+            },
             '|' => Pipe,
+            ';' => Semicolon,
+            '@' => At,
+            '$' => Dollar,
             '#' => Hash,
             c => Unknown(&self.input[0..c.len_utf8()]),
         };
-        self.input = &self.input[offset..];
+        self.advance(offset);
         Some(result)
     }
     #[inline]
+    fn advance(&mut self, offset: usize) {
+        self.input = &self.input[offset..];
+        self.offset += offset;
+    }
+    #[inline]
     fn advance_and_return(&mut self, offset: usize) -> Option<&'a str> {
         let result = &self.input[0..offset];
-        self.input = &self.input[offset..];
+        self.advance(offset);
         Some(result)
     }
 }
 
+/// Characters of a KDL identifier which aren't part of the query syntax, a word may also contain
+/// `.` (but not `..`), wildcards, `'`, and `!`, `^` or `$` unless they start an operator
+#[inline]
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace()
+        && !c.is_control()
+        && !matches!(
+            c,
+            '\\' | '/' | '(' | ')' | '{' | '}' | '[' | ']' | ';' | '"' | '\'' | '#' | '='
+                // Query syntax
+                | '.' | '*' | '?' | '<' | '>' | '|' | '!' | '@' | '$' | '^' | '~'
+                // Disallowed in KDL: direction control characters and the byte order mark
+                | '\u{200E}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Lexer, TokenType};
     #[test]
+    fn signed_numbers() {
+        let mut lexer = Lexer::from("{-1..+2} - -a +3.5 -0x1f");
+        assert_eq!(lexer.next(), Some(TokenType::EnterCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-1")));
+        assert_eq!(lexer.next(), Some(TokenType::DoublePoint));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("+2")));
+        assert_eq!(lexer.next(), Some(TokenType::LeaveCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-a")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("+3.5")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-0x1f")));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
+    fn identifiers() {
+        let mut lexer = Lexer::from("split-direction zellij:tab-bar a,b don't é💡 a^b a!b");
+        for word in [
+            "split-direction",
+            "zellij:tab-bar",
+            "a,b",
+            "don't",
+            "é💡",
+            "a^b",
+            "a!b",
+        ] {
+            assert_eq!(lexer.next(), Some(TokenType::Alphanumeric(word)));
+        }
+        assert_eq!(lexer.next(), None);
+        let mut lexer = Lexer::from("[kebab-case!=a-b|x-y<-1]{..-2}/--verbose");
+        assert_eq!(lexer.next(), Some(TokenType::EnterSquareBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("kebab-case")));
+        assert_eq!(lexer.next(), Some(TokenType::NotEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("a-b")));
+        assert_eq!(lexer.next(), Some(TokenType::Pipe));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("x-y")));
+        assert_eq!(lexer.next(), Some(TokenType::Less));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-1")));
+        assert_eq!(lexer.next(), Some(TokenType::LeaveSquareBracket));
+        assert_eq!(lexer.next(), Some(TokenType::EnterCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::DoublePoint));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("-2")));
+        assert_eq!(lexer.next(), Some(TokenType::LeaveCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Slash));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("--verbose")));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
     fn text() {
         let mut lexer = Lexer::from("\"hello\"");
         assert_eq!(lexer.next(), Some(TokenType::String("\"hello\"")));
@@ -197,6 +424,32 @@ mod tests {
         assert_eq!(lexer.next(), None);
     }
     #[test]
+    fn quoted_texts() {
+        let mut lexer = Lexer::from(
+            r###"'it\'s' "" #"C:\path\"# ##"a"#b"## """
+  a "quoted" line
+  """ #"unterminated""###,
+        );
+        assert_eq!(lexer.next(), Some(TokenType::String(r"'it\'s'")));
+        assert_eq!(lexer.next(), Some(TokenType::String(r#""""#)));
+        assert_eq!(lexer.next(), Some(TokenType::String(r##"#"C:\path\"#"##)));
+        assert_eq!(lexer.next(), Some(TokenType::String(r###"##"a"#b"##"###)));
+        assert_eq!(
+            lexer.next(),
+            Some(TokenType::String("\"\"\"\n  a \"quoted\" line\n  \"\"\""))
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(TokenType::String(r##"#"unterminated""##))
+        );
+        assert_eq!(lexer.next(), None);
+        let mut lexer = Lexer::from("#true #");
+        assert_eq!(lexer.next(), Some(TokenType::Hash));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("true")));
+        assert_eq!(lexer.next(), Some(TokenType::Hash));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
     fn alpha() {
         let mut lexer = Lexer::from("abc");
         assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("abc")));
@@ -287,6 +540,25 @@ mod tests {
         assert_eq!(lexer.next(), None);
     }
     #[test]
+    fn comparisons() {
+        let mut lexer = Lexer::from("a<1 b<=2 c >= 4 d!=5 >!");
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("a")));
+        assert_eq!(lexer.next(), Some(TokenType::Less));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("1")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("b")));
+        assert_eq!(lexer.next(), Some(TokenType::LessEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("2")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("c")));
+        assert_eq!(lexer.next(), Some(TokenType::GreaterEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("4")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("d")));
+        assert_eq!(lexer.next(), Some(TokenType::NotEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("5")));
+        assert_eq!(lexer.next(), Some(TokenType::Greater));
+        assert_eq!(lexer.next(), Some(TokenType::Bang));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
     fn token_curly_brackets() {
         let mut lexer = Lexer::from("{}");
         assert_eq!(lexer.next(), Some(TokenType::EnterCurlyBracket));
@@ -305,4 +577,75 @@ mod tests {
         assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("2")));
         assert_eq!(lexer.next(), None);
     }
+    #[test]
+    fn spans() {
+        use super::Token;
+        let mut lexer = Lexer::from(" node[ \"é\" ]/**");
+        let mut next = || {
+            lexer
+                .next_token()
+                .map(|Token { token, span }| (token, span))
+        };
+        assert_eq!(next(), Some((TokenType::Alphanumeric("node"), 1..5)));
+        assert_eq!(next(), Some((TokenType::EnterSquareBracket, 5..6)));
+        assert_eq!(next(), Some((TokenType::String("\"é\""), 7..11)));
+        assert_eq!(next(), Some((TokenType::LeaveSquareBracket, 12..13)));
+        assert_eq!(next(), Some((TokenType::Slash, 13..14)));
+        assert_eq!(next(), Some((TokenType::DoubleStar, 14..16)));
+        assert_eq!(next(), None);
+    }
+    #[test]
+    fn patterns() {
+        let mut lexer = Lexer::from(r"env_* *_env a?c/*/**/ ~/^pane(_\d+)?$/ ~/a\/b/ ~/x");
+        assert_eq!(lexer.next(), Some(TokenType::Glob("env_*")));
+        assert_eq!(lexer.next(), Some(TokenType::Glob("*_env")));
+        assert_eq!(lexer.next(), Some(TokenType::Glob("a?c")));
+        assert_eq!(lexer.next(), Some(TokenType::Slash));
+        assert_eq!(lexer.next(), Some(TokenType::Star));
+        assert_eq!(lexer.next(), Some(TokenType::Slash));
+        assert_eq!(lexer.next(), Some(TokenType::DoubleStar));
+        assert_eq!(lexer.next(), Some(TokenType::Slash));
+        assert_eq!(lexer.next(), Some(TokenType::Regex(r"~/^pane(_\d+)?$/")));
+        assert_eq!(lexer.next(), Some(TokenType::Regex(r"~/a\/b/")));
+        assert_eq!(lexer.next(), Some(TokenType::Regex("~/x")));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
+    fn string_matches() {
+        let mut lexer = Lexer::from("a^=b c$=d e*=f g~=h env_*=i *= ^ ~ $ @");
+        for (name, operator, value) in [
+            ("a", TokenType::CaretEqual, "b"),
+            ("c", TokenType::DollarEqual, "d"),
+            ("e", TokenType::StarEqual, "f"),
+            ("g", TokenType::TildeEqual, "h"),
+        ] {
+            assert_eq!(lexer.next(), Some(TokenType::Alphanumeric(name)));
+            assert_eq!(lexer.next(), Some(operator));
+            assert_eq!(lexer.next(), Some(TokenType::Alphanumeric(value)));
+        }
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("env_")));
+        assert_eq!(lexer.next(), Some(TokenType::StarEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("i")));
+        assert_eq!(lexer.next(), Some(TokenType::StarEqual));
+        assert_eq!(lexer.next(), Some(TokenType::Unknown("^")));
+        assert_eq!(lexer.next(), Some(TokenType::Unknown("~")));
+        assert_eq!(lexer.next(), Some(TokenType::Dollar));
+        assert_eq!(lexer.next(), Some(TokenType::At));
+        assert_eq!(lexer.next(), None);
+    }
+    #[test]
+    fn axes() {
+        let mut lexer = Lexer::from("ancestor::a/following-sibling::*{0} a:: b");
+        assert_eq!(lexer.next(), Some(TokenType::Axis("ancestor")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("a")));
+        assert_eq!(lexer.next(), Some(TokenType::Slash));
+        assert_eq!(lexer.next(), Some(TokenType::Axis("following-sibling")));
+        assert_eq!(lexer.next(), Some(TokenType::Star));
+        assert_eq!(lexer.next(), Some(TokenType::EnterCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("0")));
+        assert_eq!(lexer.next(), Some(TokenType::LeaveCurlyBracket));
+        assert_eq!(lexer.next(), Some(TokenType::Axis("a")));
+        assert_eq!(lexer.next(), Some(TokenType::Alphanumeric("b")));
+        assert_eq!(lexer.next(), None);
+    }
 }
@@ -1,144 +1,528 @@
-use super::{string, ParseError, Result, Value};
-use crate::lexer::TokenType;
+use super::{string, ParseErrorKind, Pattern, Result, Value};
+use crate::lexer::{Lexer, Span, Token, TokenType};
 use std::{borrow::Cow, fmt::Display};
 
+/// How an entry value is compared to the expected value
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Operator {
+    /// "="
+    #[default]
+    Equal,
+    /// "!="
+    NotEqual,
+    /// "<"
+    Less,
+    /// "<="
+    LessEqual,
+    /// ">"
+    Greater,
+    /// ">="
+    GreaterEqual,
+    /// "^=" String starting with
+    StartsWith,
+    /// "$=" String ending with
+    EndsWith,
+    /// "*=" String containing
+    Contains,
+    /// "~=" String matching a regular expression
+    Matches,
+}
+
+impl Operator {
+    fn from_token(token: &TokenType) -> Option<Self> {
+        match token {
+            TokenType::Equal => Some(Self::Equal),
+            TokenType::NotEqual => Some(Self::NotEqual),
+            TokenType::Less => Some(Self::Less),
+            TokenType::LessEqual => Some(Self::LessEqual),
+            TokenType::Greater => Some(Self::Greater),
+            TokenType::GreaterEqual => Some(Self::GreaterEqual),
+            TokenType::CaretEqual => Some(Self::StartsWith),
+            TokenType::DollarEqual => Some(Self::EndsWith),
+            TokenType::StarEqual => Some(Self::Contains),
+            TokenType::TildeEqual => Some(Self::Matches),
+            _ => None,
+        }
+    }
+    /// Whether the operator only applies to strings
+    fn is_string_match(self) -> bool {
+        matches!(
+            self,
+            Self::StartsWith | Self::EndsWith | Self::Contains | Self::Matches
+        )
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Equal => write!(f, "="),
+            Self::NotEqual => write!(f, "!="),
+            Self::Less => write!(f, "<"),
+            Self::LessEqual => write!(f, "<="),
+            Self::Greater => write!(f, ">"),
+            Self::GreaterEqual => write!(f, ">="),
+            Self::StartsWith => write!(f, "^="),
+            Self::EndsWith => write!(f, "$="),
+            Self::Contains => write!(f, "*="),
+            Self::Matches => write!(f, "~="),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum EntryKind<'a> {
     Argument {
         position: u64,
+        operator: Operator,
         value: Option<Value<'a>>,
+        /// "(<type>)" Annotation the value must have, like `u8` in `(u8)255`
+        annotation: Option<Cow<'a, str>>,
     },
     Property {
         name: Cow<'a, str>,
+        operator: Operator,
         value: Option<Value<'a>>,
+        /// "(<type>)" Annotation the value must have, like `u8` in `(u8)255`
+        annotation: Option<Cow<'a, str>>,
     },
 }
 
+/// Writes the expected value of an entry, `_` for any value, after its annotation if any
+fn fmt_value(
+    f: &mut std::fmt::Formatter<'_>,
+    annotation: &Option<Cow<'_, str>>,
+    value: &Option<Value<'_>>,
+) -> std::fmt::Result {
+    if let Some(annotation) = annotation {
+        write!(f, "(")?;
+        string::fmt_text(f, annotation)?;
+        write!(f, ")")?;
+    }
+    match value {
+        Some(value) => write!(f, "{value}"),
+        None => write!(f, "_"),
+    }
+}
+
+/// A filter on the entries of a node
 #[derive(Clone, PartialEq, Debug)]
-pub struct Entries<'a>(Vec<EntryKind<'a>>);
+pub enum EntryFilter<'a> {
+    /// "<entry>" The entry must match
+    Entry(EntryKind<'a>),
+    /// "!<filter>" The filter must not match
+    Not(Box<EntryFilter<'a>>),
+    /// "(<entries> | <entries>)" Any of the alternatives must match
+    Any(Vec<Entries<'a>>),
+}
+
+/// Entries filters, every filter must match
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Entries<'a>(Vec<EntryFilter<'a>>);
 
 impl<'a> Display for Entries<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[ ")?;
-        for (i, v) in self.0.iter().enumerate() {
-            match v {
-                EntryKind::Argument { position, value } if i != (*position as _) => write!(
-                    f,
-                    "{}={}",
-                    position,
-                    value.as_ref().unwrap_or(&Value::String(Cow::Borrowed("_")))
-                ),
-                EntryKind::Argument { value, .. } => write!(
-                    f,
-                    "{} ",
-                    value.as_ref().unwrap_or(&Value::String(Cow::Borrowed("_")))
-                ),
-                EntryKind::Property { name, value } => write!(
-                    f,
-                    "{}={} ",
-                    name,
-                    value.as_ref().unwrap_or(&Value::String(Cow::Borrowed("_")))
-                ), // TODO: quote string if not alphanumeric
-            }?;
-        }
+        self.fmt_filters(f, &mut 0)?;
         write!(f, "]")
     }
 }
+impl<'a> Entries<'a> {
+    /// Writes the filters, `arg_pos` being the position of the next positional argument
+    fn fmt_filters(&self, f: &mut std::fmt::Formatter<'_>, arg_pos: &mut u64) -> std::fmt::Result {
+        for filter in &self.0 {
+            filter.fmt_filter(f, arg_pos)?;
+            write!(f, " ")?;
+        }
+        Ok(())
+    }
+}
+impl<'a> EntryFilter<'a> {
+    fn fmt_filter(&self, f: &mut std::fmt::Formatter<'_>, arg_pos: &mut u64) -> std::fmt::Result {
+        match self {
+            EntryFilter::Entry(EntryKind::Argument {
+                position,
+                operator: Operator::Equal,
+                value,
+                annotation,
+            }) if *position == *arg_pos => {
+                *arg_pos += 1;
+                fmt_value(f, annotation, value)
+            }
+            EntryFilter::Entry(EntryKind::Argument {
+                position,
+                operator,
+                value,
+                annotation,
+            }) => {
+                write!(f, "{position}{operator}")?;
+                fmt_value(f, annotation, value)
+            }
+            EntryFilter::Entry(EntryKind::Property {
+                name,
+                operator,
+                value,
+                annotation,
+            }) => {
+                string::fmt_text(f, name)?;
+                write!(f, "{operator}")?;
+                fmt_value(f, annotation, value)
+            }
+            EntryFilter::Not(filter) => {
+                write!(f, "!")?;
+                filter.fmt_filter(f, arg_pos)
+            }
+            EntryFilter::Any(alternatives) => {
+                write!(f, "( ")?;
+                let start_pos = *arg_pos;
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "| ")?;
+                    }
+                    let mut pos = start_pos;
+                    alternative.fmt_filters(f, &mut pos)?;
+                    *arg_pos = (*arg_pos).max(pos);
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
 impl<'a> From<Vec<EntryKind<'a>>> for Entries<'a> {
     fn from(value: Vec<EntryKind<'a>>) -> Self {
+        Entries(value.into_iter().map(EntryFilter::Entry).collect())
+    }
+}
+impl<'a> From<Vec<EntryFilter<'a>>> for Entries<'a> {
+    fn from(value: Vec<EntryFilter<'a>>) -> Self {
         Entries(value)
     }
 }
 
+/// What closes a list of entries
+#[derive(Clone, Copy)]
+enum Closing {
+    /// `]`, or the end of the input if the bracket position is unknown
+    Bracket(Option<usize>),
+    /// `)`, opened at this position
+    Parenthesis(usize),
+}
+
+/// Token which ended a list of entries
+enum Ending {
+    /// `|`, another alternative follows
+    Pipe(Span),
+    /// `]`, `)` or the end of the input
+    Close,
+}
+
 impl<'a> Entries<'a> {
     pub fn new() -> Self {
         Default::default()
     }
-    pub fn parse_lexer(lexer: &mut impl Iterator<Item = TokenType<'a>>) -> Result<'a, Self> {
-        let mut entries = vec![];
-        let mut arg_pos = 0;
-        let mut prop_name = None;
-        let mut is_unnamed_arg = false;
-        let mut is_keyword = false;
-        while let Some(token) = lexer.next() {
-            let value = match token {
-                TokenType::LeaveSquareBracket => break,
-                TokenType::Hash => {
-                    if is_keyword {
-                        return Err(ParseError::UnexpectedToken(token));
-                    }
-                    is_keyword = true;
-                    continue;
-                }
-                TokenType::Alphanumeric(s) if is_keyword => {
-                    is_keyword = false;
-                    is_unnamed_arg = true;
-                    Some(string::parse_keyword(s)?)
-                }
-                TokenType::Alphanumeric(s) => {
-                    is_unnamed_arg = true;
-                    let v = string::parse_alphanumeric(s).map_err(|e| e.into_parse_error(s))?;
-                    (!matches!(v, Value::String(ref s) if s == "_")).then(|| v)
+    /// Parses entries until `]` or the end of the input
+    pub fn parse_lexer(lexer: &mut Lexer<'a>) -> Result<'a, Self> {
+        Self::parse_until(lexer, None)
+    }
+    /// Parses entries which must be closed by `]`, `open_start` being the position of `[`
+    pub(crate) fn parse_enclosed(lexer: &mut Lexer<'a>, open_start: usize) -> Result<'a, Self> {
+        Self::parse_until(lexer, Some(open_start))
+    }
+    fn parse_until(lexer: &mut Lexer<'a>, open_start: Option<usize>) -> Result<'a, Self> {
+        let mut alternatives =
+            Self::parse_alternatives(lexer, &mut 0, Closing::Bracket(open_start))?;
+        if alternatives.len() == 1 {
+            return Ok(alternatives.pop().unwrap_or_default());
+        }
+        Ok(Entries(vec![EntryFilter::Any(alternatives)]))
+    }
+    /// Parses entries separated by `|`. Each alternative starts at the same argument position,
+    /// the following arguments start after the longest alternative.
+    fn parse_alternatives(
+        lexer: &mut Lexer<'a>,
+        arg_pos: &mut u64,
+        closing: Closing,
+    ) -> Result<'a, Vec<Self>> {
+        let start_pos = *arg_pos;
+        let mut alternatives = vec![];
+        loop {
+            let mut pos = start_pos;
+            let (entries, ending) = Self::parse_conjunction(lexer, &mut pos, closing)?;
+            *arg_pos = (*arg_pos).max(pos);
+            alternatives.push(entries);
+            match ending {
+                Ending::Pipe(span) if alternatives.last().is_some_and(|e| e.0.is_empty()) => {
+                    return Err(ParseErrorKind::EmptyAlternative.at(span))
                 }
-                TokenType::String(s) => {
-                    is_unnamed_arg = true;
-                    Some(Value::String(
-                        string::parse_string(s).map_err(|e| e.into_parse_error(s))?,
-                    ))
+                Ending::Pipe(_) => (),
+                Ending::Close
+                    if alternatives.len() > 1 && alternatives.iter().any(|e| e.0.is_empty()) =>
+                {
+                    return Err(ParseErrorKind::EmptyAlternative.at(lexer.offset()..lexer.offset()))
                 }
-                TokenType::Equal => {
-                    if prop_name.is_some() {
-                        return Err(ParseError::DoubleEqual);
+                Ending::Close => return Ok(alternatives),
+            }
+        }
+    }
+    fn parse_conjunction(
+        lexer: &mut Lexer<'a>,
+        arg_pos: &mut u64,
+        closing: Closing,
+    ) -> Result<'a, (Self, Ending)> {
+        let mut filters = vec![];
+        loop {
+            let Some(Token { token, span }) = lexer.next_token() else {
+                return match closing {
+                    Closing::Bracket(None) => Ok((Entries(filters), Ending::Close)),
+                    Closing::Bracket(Some(start)) => {
+                        Err(ParseErrorKind::UnterminatedEntries.at(start..lexer.offset()))
                     }
-                    let Some(EntryKind::Argument { position: _, value }) = entries.pop() else {
-                        return Err(ParseError::MissingEntryIdentifier);
-                    };
-                    if !is_unnamed_arg {
-                        return Err(ParseError::MissingEntryIdentifier);
+                    Closing::Parenthesis(start) => {
+                        Err(ParseErrorKind::UnterminatedGroup.at(start..lexer.offset()))
                     }
-                    prop_name = Some(value);
-                    arg_pos -= 1;
-                    is_unnamed_arg = false;
-                    continue;
-                }
-                t => return Err(ParseError::UnexpectedToken(t)),
+                };
             };
-            match prop_name {
-                Some(None) => return Err(ParseError::UndefinedEntryIdentifier),
-                Some(Some(Value::String(name))) => {
-                    entries.push(EntryKind::Property { name, value });
-                    prop_name = None;
-                    is_unnamed_arg = false;
-                }
-                Some(Some(Value::Integer(position))) => {
-                    entries.push(EntryKind::Argument {
-                        position: position as _,
-                        value,
-                    });
-                    prop_name = None;
-                    is_unnamed_arg = false;
+            match (token, closing) {
+                (TokenType::LeaveSquareBracket, Closing::Bracket(_))
+                | (TokenType::LeaveParenthesis, Closing::Parenthesis(_)) => {
+                    return Ok((Entries(filters), Ending::Close))
                 }
-                Some(Some(s @ _)) => return Err(ParseError::UnexpectedEntryIdentifier(s)),
-                None => {
-                    entries.push(EntryKind::Argument {
-                        position: arg_pos,
-                        value,
-                    });
-                    arg_pos += 1;
+                (TokenType::LeaveSquareBracket, Closing::Parenthesis(start)) => {
+                    return Err(ParseErrorKind::UnterminatedGroup.at(start..span.end))
                 }
+                (TokenType::Pipe, _) => return Ok((Entries(filters), Ending::Pipe(span))),
+                (token, _) => filters.push(Self::parse_filter(lexer, token, span, arg_pos)?),
             }
         }
-        if prop_name.is_some() {
-            return Err(ParseError::MissingEntryValue);
+    }
+    fn parse_filter(
+        lexer: &mut Lexer<'a>,
+        token: TokenType<'a>,
+        span: Span,
+        arg_pos: &mut u64,
+    ) -> Result<'a, EntryFilter<'a>> {
+        match token {
+            TokenType::Bang => {
+                let filter = match lexer.next_token() {
+                    Some(Token {
+                        token:
+                            TokenType::Pipe
+                            | TokenType::LeaveSquareBracket
+                            | TokenType::LeaveParenthesis,
+                        ..
+                    })
+                    | None => return Err(ParseErrorKind::MissingNegatedEntry.at(span)),
+                    Some(Token { token, span }) => Self::parse_filter(lexer, token, span, arg_pos)?,
+                };
+                Ok(EntryFilter::Not(Box::new(filter)))
+            }
+            // `(u8)255` is an annotated value, `(a | b)` a group
+            TokenType::EnterParenthesis if Self::is_annotation(lexer) => {
+                let annotation = Self::parse_annotation(lexer, span.start)?;
+                let Some(Token { token, span }) = lexer.next_token() else {
+                    unreachable!("an annotation is followed by a value");
+                };
+                Self::parse_entry(lexer, token, span, arg_pos, Some(annotation))
+                    .map(EntryFilter::Entry)
+            }
+            TokenType::EnterParenthesis => Ok(EntryFilter::Any(Self::parse_alternatives(
+                lexer,
+                arg_pos,
+                Closing::Parenthesis(span.start),
+            )?)),
+            token if Operator::from_token(&token).is_some() => {
+                Err(ParseErrorKind::MissingEntryIdentifier.at(span))
+            }
+            token => Self::parse_entry(lexer, token, span, arg_pos, None).map(EntryFilter::Entry),
         }
-        Ok(Entries(entries))
     }
-    pub fn entries(&self) -> &[EntryKind] {
-        &self.0
+    /// Checks whether the lexer, after `(`, is at an annotation: a type name and `)` directly
+    /// followed by a value. Otherwise `(` starts a group of alternatives
+    fn is_annotation(lexer: &Lexer<'a>) -> bool {
+        let mut peek = lexer.clone();
+        let (
+            Some(Token {
+                token: TokenType::Alphanumeric(_) | TokenType::String(_),
+                ..
+            }),
+            Some(Token {
+                token: TokenType::LeaveParenthesis,
+                span: close,
+            }),
+            Some(Token {
+                token: TokenType::Alphanumeric(_) | TokenType::String(_) | TokenType::Hash,
+                span,
+            }),
+        ) = (peek.next_token(), peek.next_token(), peek.next_token())
+        else {
+            return false;
+        };
+        close.end == span.start
     }
-}
-impl<'a> Default for Entries<'a> {
-    fn default() -> Self {
-        Self(Vec::new())
+    /// Parses the type name of an annotation and its closing `)`, `open` being the position of `(`
+    pub(crate) fn parse_annotation(lexer: &mut Lexer<'a>, open: usize) -> Result<'a, Cow<'a, str>> {
+        let annotation = match lexer.next_token() {
+            Some(Token {
+                token: TokenType::Alphanumeric(s),
+                ..
+            }) => Cow::Borrowed(s),
+            Some(Token {
+                token: TokenType::String(s),
+                span,
+            }) => string::parse_string(s).map_err(|e| e.into_parse_error(s).at(span))?,
+            Some(Token { span, .. }) => {
+                return Err(ParseErrorKind::MissingAnnotation.at(open..span.end))
+            }
+            None => return Err(ParseErrorKind::UnterminatedAnnotation.at(open..lexer.offset())),
+        };
+        match lexer.next_token() {
+            Some(Token {
+                token: TokenType::LeaveParenthesis,
+                ..
+            }) => Ok(annotation),
+            _ => Err(ParseErrorKind::UnterminatedAnnotation.at(open..lexer.offset())),
+        }
+    }
+    /// Parses a positional argument (`value`), an indexed argument (`index=value`) or a property
+    /// (`name=value`), `token` being the first token of the entry, after its annotation if any.
+    fn parse_entry(
+        lexer: &mut Lexer<'a>,
+        token: TokenType<'a>,
+        span: Span,
+        arg_pos: &mut u64,
+        annotation: Option<Cow<'a, str>>,
+    ) -> Result<'a, EntryKind<'a>> {
+        let (identifier, ident_span) = Self::parse_value(lexer, token, span)?;
+
+        let mut peek = lexer.clone();
+        let operator = match peek.next_token() {
+            Some(Token { token, span }) => Operator::from_token(&token).map(|op| (op, span)),
+            None => None,
+        };
+        let Some((operator, operator_span)) = operator else {
+            let position = *arg_pos;
+            *arg_pos += 1;
+            return Ok(EntryKind::Argument {
+                position,
+                operator: Operator::Equal,
+                value: identifier,
+                annotation,
+            });
+        };
+        *lexer = peek;
+
+        let mut value_token = lexer.next_token();
+        let mut annotation = annotation;
+        if let Some(Token {
+            token: TokenType::EnterParenthesis,
+            span,
+        }) = value_token
+        {
+            if annotation.is_some() {
+                return Err(ParseErrorKind::AnnotationAlreadyDefined.at(span));
+            }
+            annotation = Some(Self::parse_annotation(lexer, span.start)?);
+            value_token = lexer.next_token();
+        }
+        let (value, value_span) = match value_token {
+            None
+            | Some(Token {
+                token: TokenType::LeaveSquareBracket | TokenType::LeaveParenthesis | TokenType::Pipe,
+                ..
+            }) => {
+                return Err(
+                    ParseErrorKind::MissingEntryValue.at(ident_span.start..operator_span.end)
+                )
+            }
+            Some(Token { token, span }) if Operator::from_token(&token).is_some() => {
+                return Err(ParseErrorKind::DoubleEqual.at(span))
+            }
+            // Regular expressions keep their escapes, so `~="\.wasm"` matches a literal dot
+            Some(Token {
+                token: TokenType::String(s),
+                span,
+            }) if operator == Operator::Matches => {
+                let pattern = string::string_contents(s)
+                    .map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+                (Some(Value::String(pattern)), span)
+            }
+            // Bare words are matched as text, so `version^=1.2` matches "1.2.3"
+            Some(Token {
+                token: TokenType::Alphanumeric(s),
+                span,
+            }) if operator.is_string_match() && s != "_" => {
+                (Some(Value::String(Cow::Borrowed(s))), span)
+            }
+            Some(Token { token, span }) => Self::parse_value(lexer, token, span)?,
+        };
+        let value = match value {
+            None if operator != Operator::Equal => {
+                return Err(ParseErrorKind::MissingComparisonValue(operator).at(value_span))
+            }
+            Some(Value::String(s)) if operator == Operator::Matches => Some(Value::Regex(
+                Pattern::new(&s).map_err(|e| e.at(value_span))?,
+            )),
+            Some(v) if operator.is_string_match() && !matches!(v, Value::String(_)) => {
+                return Err(ParseErrorKind::MatchExpectingString(operator, v).at(value_span))
+            }
+            value => value,
+        };
+        match identifier {
+            None => Err(ParseErrorKind::UndefinedEntryIdentifier.at(ident_span)),
+            Some(Value::String(name)) => Ok(EntryKind::Property {
+                name,
+                operator,
+                value,
+                annotation,
+            }),
+            Some(Value::Integer(position)) => match position.try_into() {
+                Ok(position) => Ok(EntryKind::Argument {
+                    position,
+                    operator,
+                    value,
+                    annotation,
+                }),
+                Err(_) => Err(ParseErrorKind::NegativeArgumentIndex(position).at(ident_span)),
+            },
+            Some(s) => Err(ParseErrorKind::UnexpectedEntryIdentifier(s).at(ident_span)),
+        }
+    }
+    /// Parses a value, `_` giving `None`
+    fn parse_value(
+        lexer: &mut Lexer<'a>,
+        token: TokenType<'a>,
+        span: Span,
+    ) -> Result<'a, (Option<Value<'a>>, Span)> {
+        match token {
+            TokenType::Hash => {
+                let Some(Token {
+                    token: TokenType::Alphanumeric(s),
+                    span: keyword_span,
+                }) = lexer.next_token()
+                else {
+                    return Err(ParseErrorKind::MissingKeyword.at(span));
+                };
+                let span = span.start..keyword_span.end;
+                let v = string::parse_keyword(s).map_err(|e| e.at(span.clone()))?;
+                Ok((Some(v), span))
+            }
+            TokenType::Alphanumeric(s) => {
+                let v = string::parse_alphanumeric(s)
+                    .map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+                Ok((
+                    (!matches!(v, Value::String(ref s) if s == "_")).then_some(v),
+                    span,
+                ))
+            }
+            TokenType::String(s) => {
+                let v =
+                    string::parse_string(s).map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+                Ok((Some(Value::String(v)), span))
+            }
+            t => Err(ParseErrorKind::UnexpectedToken(t).at(span)),
+        }
+    }
+    pub fn entries(&self) -> &[EntryFilter<'_>] {
+        &self.0
     }
 }
@@ -1,49 +1,66 @@
-use super::Value;
-use crate::lexer::TokenType;
+use super::{Operator, Value};
+use crate::lexer::{Span, TokenType};
+use std::fmt::Display;
 
 #[derive(thiserror::Error, Clone, Debug, PartialEq)]
 pub enum ParseStringError {
-    #[error("The string is empty")]
+    #[error("the string is empty")]
     EmptyString,
-    #[error("The string misses \" at the beginning")]
+    #[error("the string misses \" at the beginning")]
     MissingBeginOfString,
-    #[error("The string misses \" at the end")]
+    #[error("the string misses \" at the end")]
     MissingEndOfString,
-    #[error("This escape does not exists: \\{0}")]
+    #[error("a multi-line string must start with a new line")]
+    MultiLineStart,
+    #[error("the last line of a multi-line string must only have whitespaces")]
+    MultiLineEnd,
+    #[error("every line of a multi-line string must start with the whitespaces of its last line")]
+    MultiLineIndent,
+    #[error("this escape does not exist: \\{0}")]
     UnknownEscape(char),
-    #[error("Expected hexadecimal number, but had something else")]
+    #[error("expected hexadecimal number, but had something else")]
     NotHexDigit,
-    #[error("Ascii escape code not valid: \\x{0:02X}")]
+    #[error("ascii escape code not valid: \\x{0:02X}")]
     AsciiNotValid(u8),
-    #[error("Unicode escape code not valid: \\u{{{0:X}}}")]
+    #[error("unicode escape code not valid: \\u{{{0:X}}}")]
     UnicodeNotValid(u32),
-    #[error("Unicode escape code must have at most 6 digits")]
+    #[error("unicode escape code must have at most 6 digits")]
     UnicodeMoreThanSixDigits,
-    #[error("Unicode escape code must be at most 0x10FFFF (found \\u{{{0}}})")]
+    #[error("unicode escape code must be at most 0x10FFFF (found \\u{{{0}}})")]
     UnicodeOutOfBound(u32),
-    #[error("A curly bracket is missing")]
+    #[error("a curly bracket is missing")]
     ExpectedCurlyBracket,
-    #[error("Number detected as malformed")]
+    #[error("number detected as malformed")]
     MalformedNumber,
 }
 
 impl ParseStringError {
     #[inline]
-    pub fn into_parse_error<'a>(self, origin: &'a str) -> ParseError<'a> {
-        ParseError::MalformedString(origin, self)
+    pub fn into_parse_error(self, origin: &str) -> ParseErrorKind<'_> {
+        ParseErrorKind::MalformedString(origin, self)
     }
 }
 
+/// Error of the query parser, located in the query by its span
 #[derive(thiserror::Error, PartialEq, Debug)]
-pub enum ParseError<'a> {
+#[error("{kind}")]
+pub struct ParseError<'a> {
+    pub kind: ParseErrorKind<'a>,
+    pub span: Span,
+}
+
+#[derive(thiserror::Error, PartialEq, Debug)]
+pub enum ParseErrorKind<'a> {
     #[error("unexpected token: {0}")]
     UnexpectedToken(TokenType<'a>),
-    #[error("The string \"{0}\" is malformed: {1}")]
+    #[error("the string \"{0}\" is malformed: {1}")]
     MalformedString(&'a str, ParseStringError),
-    #[error("double equal in entries")]
+    #[error("double comparison operator in entries")]
     DoubleEqual,
     #[error("missing entry value after an equal")]
     MissingEntryValue,
+    #[error("the comparison {0} needs a value, _ can only be used with =")]
+    MissingComparisonValue(Operator),
     #[error("entry must be identified by string (property name) or integer number (argument index), got {0}")]
     UnexpectedEntryIdentifier(Value<'a>),
     #[error("use of _ joker in entry identifier")]
@@ -56,20 +73,135 @@ pub enum ParseError<'a> {
     NotANode,
     #[error("missing node before entries")]
     MissingNode,
-    #[error("A node is already defined before")]
+    #[error("a node is already defined before")]
     NodeAlreadyDefined,
-    #[error("The entries was already defined for this node")]
+    #[error("the entries were already defined for this node")]
     EntriesAlreadyDefined,
-    #[error("The range was already defined for this node")]
+    #[error("the range was already defined for this node")]
     RangeAlreadyDefined,
     #[error("expected an integer number, got: {0}")]
     RangeExpectingInteger(Value<'a>),
-    #[error("The range is empty")]
+    #[error("the range is empty")]
     RangeEmpty,
-    #[error("The range separator (between numbers) is missing")]
+    #[error("the range separator (between numbers) is missing")]
     RangeMissingSeparator,
+    #[error("the end of the range is missing after ..= or ..<")]
+    RangeMissingEnd,
+    #[error("missing step after ;")]
+    MissingRangeStep,
+    #[error("the step of a range must be a positive integer, got {0}")]
+    InvalidRangeStep(Value<'a>),
+    #[error("expecting a string to match with {0}, got {1}")]
+    MatchExpectingString(Operator, Value<'a>),
+    #[error("the regular expression misses / at the end")]
+    UnterminatedRegex,
+    #[error("invalid regular expression: {0}")]
+    InvalidRegex(regex::Error),
     #[error("unknown keyword: #{0}")]
     UnknownKeyword(&'a str),
+    #[error("missing keyword after #")]
+    MissingKeyword,
+    #[error("an alternative of the entries is empty")]
+    EmptyAlternative,
+    #[error("the group of entries is not closed by )")]
+    UnterminatedGroup,
+    #[error("missing entry after !")]
+    MissingNegatedEntry,
+    #[error("unknown axis: {0}::")]
+    UnknownAxis(&'a str),
+    #[error("missing node name, glob, regex or * after the axis")]
+    MissingAxisNode,
+    #[error("unknown order: {0}, expected pre, post, breadth or tree")]
+    UnknownOrder(&'a str),
+    #[error("the order was already defined for this **")]
+    OrderAlreadyDefined,
+    #[error("the options of ** are not closed by >")]
+    UnterminatedDescent,
+    #[error("depth must be positive, got {0}")]
+    NegativeDepth(i128),
+    #[error("a depth can't have a step")]
+    SteppedDepth,
+    #[error("missing nodes before the projection")]
+    ProjectionWithoutNode,
+    #[error("missing entry to select after {0}")]
+    MissingProjectedEntry(TokenType<'a>),
+    #[error("the projection must be the last segment of the path")]
+    ProjectionNotLast,
+    #[error("the query is empty")]
+    EmptyQuery,
+    #[error("missing node between slashes")]
+    EmptySegment,
+    #[error("the range is not closed by }}")]
+    UnterminatedRange,
+    #[error("the entries are not closed by ]")]
+    UnterminatedEntries,
+    #[error("argument index must be positive, got {0}")]
+    NegativeArgumentIndex(i128),
+    #[error("missing type name in the annotation")]
+    MissingAnnotation,
+    #[error("the annotation is not closed by )")]
+    UnterminatedAnnotation,
+    #[error("the annotation was already defined for this entry")]
+    AnnotationAlreadyDefined,
+    #[error("missing node after the annotation")]
+    MissingAnnotatedNode,
+}
+
+impl<'a> ParseErrorKind<'a> {
+    #[inline]
+    pub fn at(self, span: Span) -> ParseError<'a> {
+        ParseError { kind: self, span }
+    }
+}
+
+impl<'a> ParseError<'a> {
+    /// Displays the error with the query line and the span underlined, like:
+    ///
+    /// ```text
+    /// error: unexpected token: ]
+    ///   |
+    ///   | node/[1]
+    ///   |      ^
+    /// ```
+    #[inline]
+    pub fn report<'b>(&'b self, query: &'b str) -> Report<'b> {
+        Report { error: self, query }
+    }
+}
+
+/// Caret-style rendering of a [`ParseError`], see [`ParseError::report`]
+pub struct Report<'b> {
+    error: &'b ParseError<'b>,
+    query: &'b str,
+}
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let start = floor_char_boundary(self.query, self.error.span.start);
+        let end = floor_char_boundary(self.query, self.error.span.end).max(start);
+        let line_start = self.query[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.query[start..]
+            .find('\n')
+            .map_or(self.query.len(), |i| start + i);
+        let line = &self.query[line_start..line_end];
+        let padding = self.query[line_start..start].chars().count();
+        let underline = self.query[start..end.min(line_end)].chars().count().max(1);
+
+        writeln!(f, "error: {}", self.error)?;
+        writeln!(f, "  |")?;
+        writeln!(f, "  | {line}")?;
+        write!(f, "  | {:padding$}{:^<underline$}", "", "")
+    }
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
 }
 
 pub type Result<'a, T> = std::result::Result<T, ParseError<'a>>;
@@ -1,26 +1,47 @@
 mod entries;
 mod error;
+mod query;
 mod string;
 #[cfg(test)]
 mod tests;
 mod value;
 
-use crate::lexer::{Lexer, TokenType};
-pub use entries::{Entries, EntryKind};
-pub use error::{ParseError, Result};
-use std::{borrow::Cow, fmt::Display};
+use crate::lexer::{Lexer, Span, Token, TokenType};
+pub use entries::{Entries, EntryFilter, EntryKind, Operator};
+pub use error::{ParseError, ParseErrorKind, Report, Result};
+pub use query::Query;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    iter::{Skip, StepBy, Take},
+};
 pub use value::Value;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Node<'a> {
     pub node: NodeKind<'a>,
+    /// "(<type>)" Annotation the node must have, like `widget` in `(widget)*`
+    pub annotation: Option<Cow<'a, str>>,
     pub entries: Option<Entries<'a>>,
     pub range: Option<Range>,
 }
 
 impl<'a> Display for Node<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.node)?;
+        match (&self.annotation, &self.node) {
+            (None, node) => write!(f, "{node}")?,
+            // The annotation comes after the axis: `ancestor::(widget)*`
+            (Some(annotation), NodeKind::Axis(axis, node)) => {
+                write!(f, "{axis}::(")?;
+                string::fmt_text(f, annotation)?;
+                write!(f, "){node}")?
+            }
+            (Some(annotation), node) => {
+                write!(f, "(")?;
+                string::fmt_text(f, annotation)?;
+                write!(f, "){node}")?
+            }
+        }
         if let Some(entries) = &self.entries {
             write!(f, "{entries}")?;
         }
@@ -35,6 +56,7 @@ impl<'a> From<NodeKind<'a>> for Node<'a> {
     fn from(node: NodeKind<'a>) -> Self {
         Self {
             node,
+            annotation: None,
             entries: None,
             range: None,
         }
@@ -45,67 +67,376 @@ impl<'a> From<NodeKind<'a>> for Node<'a> {
 pub enum NodeKind<'a> {
     /// "<name>" Node with a name
     Named(Cow<'a, str>),
+    /// "<glob>" Node with a name matching a glob pattern like `env_*`, `?` matches one character
+    Glob(Cow<'a, str>),
+    /// "~/<regex>/" Node with a name matching a regular expression
+    Regex(Pattern),
     /// "*" Any nodes in the current scope
     Any,
-    /// "**" Nodes starting anywhere in the doc
-    Anywhere,
+    /// "**" Nodes starting anywhere in the doc, "**<order>" visits them in this order
+    Anywhere(Descent),
     /// ".." Parent node
     Parent,
+    /// "." Current node
+    Current,
+    /// "/" at the start of the path, top of the document
+    Root,
+    /// "<axis>::<node>" Nodes of the axis, `<node>` being a name, a glob, a regex or `*`
+    Axis(Axis, Box<NodeKind<'a>>),
+}
+
+/// Direction to look for nodes from the current node
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Axis {
+    /// "ancestor" Parent, grand-parent, etc. from the nearest
+    Ancestor,
+    /// "following-sibling" Nodes after the current node, with the same parent
+    FollowingSibling,
+    /// "preceding-sibling" Nodes before the current node, with the same parent, from the nearest
+    PrecedingSibling,
+}
+
+impl Axis {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ancestor" => Some(Self::Ancestor),
+            "following-sibling" => Some(Self::FollowingSibling),
+            "preceding-sibling" => Some(Self::PrecedingSibling),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ancestor => write!(f, "ancestor"),
+            Self::FollowingSibling => write!(f, "following-sibling"),
+            Self::PrecedingSibling => write!(f, "preceding-sibling"),
+        }
+    }
+}
+
+/// Order in which `**` visits the descendants of the current node
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Order {
+    /// "pre" A node comes before its descendants
+    #[default]
+    PreOrder,
+    /// "post" A node comes after its descendants
+    PostOrder,
+    /// "breadth" Level by level, the nearest levels first
+    BreadthFirst,
+    /// "tree" Like a file tree listing, the children of a node come before their descendants
+    FileTree,
+}
+
+impl Order {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pre" => Some(Self::PreOrder),
+            "post" => Some(Self::PostOrder),
+            "breadth" => Some(Self::BreadthFirst),
+            "tree" => Some(Self::FileTree),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PreOrder => write!(f, "pre"),
+            Self::PostOrder => write!(f, "post"),
+            Self::BreadthFirst => write!(f, "breadth"),
+            Self::FileTree => write!(f, "tree"),
+        }
+    }
+}
+
+/// Options of `**`, written between `<` and `>`
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Descent {
+    /// Order of the descendants, the one of the resolution when not defined
+    pub order: Option<Order>,
+    /// Levels below the current node where the descendants are selected, its children being at
+    /// the level 1. Both ends are included
+    pub depth: Option<Range>,
+}
+
+impl Descent {
+    /// Lowest and deepest levels selected, the descendants below the deepest are not visited
+    pub fn levels(&self) -> (usize, usize) {
+        let Some(depth) = &self.depth else {
+            return (1, usize::MAX);
+        };
+        let (from, to) = depth.indices();
+        let to = match depth {
+            Range::ToExclusive(_) | Range::BothExclusive(..) => to.map(|j| (j - 1).max(0)),
+            _ => to,
+        };
+        (
+            from.map_or(1, to_index).max(1),
+            to.map_or(usize::MAX, to_index),
+        )
+    }
+}
+
+impl Display for Descent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.order, &self.depth) {
+            (None, None) => Ok(()),
+            (Some(order), None) => write!(f, "<{order}>"),
+            (None, Some(depth)) => {
+                write!(f, "<")?;
+                depth.fmt_bounds(f)?;
+                write!(f, ">")
+            }
+            (Some(order), Some(depth)) => {
+                write!(f, "<{order} ")?;
+                depth.fmt_bounds(f)?;
+                write!(f, ">")
+            }
+        }
+    }
 }
 
 impl<'a> Display for NodeKind<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Named(s) => write!(f, "{}", s),
+            Self::Named(s) => string::fmt_text(f, s),
+            Self::Glob(s) => write!(f, "{}", s),
+            Self::Regex(r) => write!(f, "~/{}/", r.regex().as_str()),
             Self::Any => write!(f, "*"),
-            Self::Anywhere => write!(f, "**"),
+            Self::Anywhere(descent) => write!(f, "**{descent}"),
             Self::Parent => write!(f, ".."),
-            _ => todo!(),
+            Self::Current => write!(f, "."),
+            // Written by the separator following it
+            Self::Root => Ok(()),
+            Self::Axis(axis, node) => write!(f, "{}::{}", axis, node),
         }
     }
 }
 
+/// Regular expression matching node names or entry values
+#[derive(Clone, Debug)]
+pub struct Pattern(regex::Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Pattern {
+    /// Parses the pattern of a `~/<regex>/` token
+    fn parse<'a>(token: &'a str) -> std::result::Result<Self, ParseErrorKind<'a>> {
+        let pattern = token
+            .strip_prefix("~/")
+            .and_then(|p| p.strip_suffix('/'))
+            .filter(|p| p.chars().rev().take_while(|c| *c == '\\').count() % 2 == 0)
+            .ok_or(ParseErrorKind::UnterminatedRegex)?;
+        Self::new(pattern)
+    }
+    pub(crate) fn new<'a>(pattern: &str) -> std::result::Result<Self, ParseErrorKind<'a>> {
+        regex::Regex::new(pattern)
+            .map(Self)
+            .map_err(ParseErrorKind::InvalidRegex)
+    }
+    #[inline]
+    pub fn regex(&self) -> &regex::Regex {
+        &self.0
+    }
+}
+
 pub trait RangedIterator
 where
     Self: Iterator + Sized,
 {
-    fn ranged(self, range: Option<&Range>) -> impl Iterator<Item = <Self as Iterator>::Item> {
-        let Some(range) = range else {
-            return self.skip(0).take(usize::MAX);
-        };
-        match range {
-            Range::One(index) => self.skip(*index as _).take(1),
-            Range::From(from) => self.skip(*from as _).take(usize::MAX),
-            Range::To(to) => self.skip(0).take(*to as _),
-            Range::Both(from, to) => self.skip(*from as _).take((*to - *from) as _),
-            Range::All => self.skip(0).take(usize::MAX),
+    /// Selects the items of the range, the items are collected first when the range counts from
+    /// the end
+    fn ranged(self, range: Option<&Range>) -> Ranged<Self> {
+        let range = range.unwrap_or(&Range::All);
+        let step = range.step();
+        if range.from_end() {
+            let items = self.collect::<Vec<_>>();
+            let (skip, take) = range.skip_take(items.len());
+            return Ranged::Buffered(items.into_iter().skip(skip).take(take).step_by(step));
         }
+        let (skip, take) = range.skip_take(0);
+        Ranged::Lazy(self.skip(skip).take(take).step_by(step))
     }
 }
 impl<I> RangedIterator for I where I: Iterator {}
 
+/// Items of an iterator selected by a range, see [`RangedIterator::ranged`]
+pub enum Ranged<I: Iterator> {
+    Lazy(StepBy<Take<Skip<I>>>),
+    Buffered(StepBy<Take<Skip<std::vec::IntoIter<I::Item>>>>),
+}
+
+impl<I: Iterator> Iterator for Ranged<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Ranged::Lazy(it) => it.next(),
+            Ranged::Buffered(it) => it.next(),
+        }
+    }
+}
+
+/// Converts a range index, an index out of `usize` bounds selects nothing
+#[inline]
+fn to_index(index: i128) -> usize {
+    usize::try_from(index).unwrap_or(usize::MAX)
+}
+
+/// Positions of the selected nodes, a negative index counts from the end, `-1` being the last
+/// node. The ends are included unless they are written with `..<`
 #[derive(Clone, PartialEq, Debug)]
 pub enum Range {
     /// {i}
     One(i128),
     /// {i..}
     From(i128),
-    /// {..j}
+    /// {..j} or {..=j}
     To(i128),
-    /// {i..j}
+    /// {i..j} or {i..=j}
     Both(i128, i128),
+    /// {..<j}
+    ToExclusive(i128),
+    /// {i..<j}
+    BothExclusive(i128, i128),
     /// {..}
     All,
+    /// {<range>;n} Every n-th node of the range, starting with its first one
+    Stepped(Box<Range>, u64),
+}
+
+impl Range {
+    /// Start and end indices of the range, whether they are included or not
+    fn indices(&self) -> (Option<i128>, Option<i128>) {
+        match self {
+            Range::One(i) => (Some(*i), Some(*i)),
+            Range::From(i) => (Some(*i), None),
+            Range::To(j) | Range::ToExclusive(j) => (None, Some(*j)),
+            Range::Both(i, j) | Range::BothExclusive(i, j) => (Some(*i), Some(*j)),
+            Range::All => (None, None),
+            Range::Stepped(range, _) => range.indices(),
+        }
+    }
+    /// Checks whether an index counts from the end, the number of items is then needed
+    pub fn from_end(&self) -> bool {
+        let (from, to) = self.indices();
+        from.is_some_and(|i| i < 0) || to.is_some_and(|j| j < 0)
+    }
+    /// Distance between the selected items, 1 selecting all of them
+    fn step(&self) -> usize {
+        match self {
+            Range::Stepped(_, step) => usize::try_from(*step).unwrap_or(usize::MAX).max(1),
+            _ => 1,
+        }
+    }
+    /// Number of items to skip then to take among `len` items, before applying the step. `len`
+    /// is only used by the indices counting from the end
+    fn skip_take(&self, len: usize) -> (usize, usize) {
+        let position = |index: i128| match index {
+            ..0 => i128::try_from(len).unwrap_or(i128::MAX) + index,
+            _ => index,
+        };
+        // Number of items up to `to`, `to` excluded
+        let until = |from: i128, to: i128| to_index(to.saturating_sub(from).max(0));
+        match self {
+            Range::One(i) => match position(*i) {
+                ..0 => (0, 0),
+                i => (to_index(i), 1),
+            },
+            Range::From(i) => (to_index(position(*i).max(0)), usize::MAX),
+            Range::To(j) => (0, until(0, position(*j).saturating_add(1))),
+            Range::ToExclusive(j) => (0, until(0, position(*j))),
+            Range::Both(i, j) => {
+                let from = position(*i).max(0);
+                (to_index(from), until(from, position(*j).saturating_add(1)))
+            }
+            Range::BothExclusive(i, j) => {
+                let from = position(*i).max(0);
+                (to_index(from), until(from, position(*j)))
+            }
+            Range::All => (0, usize::MAX),
+            Range::Stepped(range, _) => range.skip_take(len),
+        }
+    }
+    /// Writes the range without the curly brackets
+    fn fmt_bounds(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Range::One(i) => write!(f, "{i}"),
+            Range::From(i) => write!(f, "{i}.."),
+            Range::To(j) => write!(f, "..{j}"),
+            Range::Both(i, j) => write!(f, "{i}..{j}"),
+            Range::ToExclusive(j) => write!(f, "..<{j}"),
+            Range::BothExclusive(i, j) => write!(f, "{i}..<{j}"),
+            Range::All => write!(f, ".."),
+            Range::Stepped(range, step) => {
+                range.fmt_bounds(f)?;
+                write!(f, ";{step}")
+            }
+        }
+    }
 }
 
 impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        self.fmt_bounds(f)?;
+        write!(f, "}}")
+    }
+}
+
+/// Entries selected by a projection
+#[derive(Clone, PartialEq, Debug)]
+pub enum EntrySelector<'a> {
+    /// "<index>" Argument at this position
+    Argument(u64),
+    /// "<name>" Property with this name
+    Property(Cow<'a, str>),
+    /// "*" Every entry
+    All,
+}
+
+impl<'a> Display for EntrySelector<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Argument(i) => write!(f, "{}", i),
+            Self::Property(name) => string::fmt_text(f, name),
+            Self::All => write!(f, "*"),
+        }
+    }
+}
+
+/// Last segment of a path selecting entries of the nodes instead of the nodes
+#[derive(Clone, PartialEq, Debug)]
+pub enum Projection<'a> {
+    /// "@<entry>" Selects the entries
+    Entries(EntrySelector<'a>),
+    /// "$<entry>" Selects the values of the entries
+    Values(EntrySelector<'a>),
+}
+
+impl<'a> Display for Projection<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Range::One(i) => write!(f, "{{{0}}}", i),
-            Range::From(i) => write!(f, "{{{0}..}}", i),
-            Range::To(i) => write!(f, "{{..{0}}}", i),
-            Range::Both(i, j) => write!(f, "{{{0}..{1}}}", i, j),
-            Range::All => write!(f, "{{..}}"),
+            Self::Entries(selector) => write!(f, "@{}", selector),
+            Self::Values(selector) => write!(f, "${}", selector),
+        }
+    }
+}
+
+impl<'a> Projection<'a> {
+    #[inline]
+    pub fn selector(&self) -> &EntrySelector<'a> {
+        match self {
+            Self::Entries(selector) | Self::Values(selector) => selector,
         }
     }
 }
@@ -114,12 +445,19 @@ type Selectors<'a> = Vec<Node<'a>>;
 #[derive(Clone, PartialEq, Debug)]
 pub struct Path<'a> {
     nodes: Selectors<'a>,
+    projection: Option<Projection<'a>>,
 }
 
 impl<'a> Display for Path<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for node in &self.nodes {
-            write!(f, "{node}/")?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i != 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{node}")?;
+        }
+        if let Some(projection) = &self.projection {
+            write!(f, "/{projection}")?;
         }
         Ok(())
     }
@@ -131,130 +469,540 @@ impl<'a> NodeBuilder<'a> {
     fn new() -> Self {
         Self(None)
     }
-    fn set_node(&mut self, node: NodeKind<'a>) -> Result<'a, ()> {
+    fn set_node(&mut self, node: NodeKind<'a>, span: Span) -> Result<'a, ()> {
         if self.0.is_some() {
-            return Err(ParseError::NodeAlreadyDefined);
+            return Err(ParseErrorKind::NodeAlreadyDefined.at(span));
         }
         let _ = self.0.insert(Node::from(node));
         Ok(())
     }
-    fn set_entries(&mut self, entries: Entries<'a>) -> Result<'a, ()> {
+    fn set_entries(&mut self, entries: Entries<'a>, span: Span) -> Result<'a, ()> {
         let Some(node) = self.0.as_mut() else {
-            return Err(ParseError::MissingNode);
+            return Err(ParseErrorKind::MissingNode.at(span));
         };
         if node.entries.is_some() {
-            return Err(ParseError::EntriesAlreadyDefined);
+            return Err(ParseErrorKind::EntriesAlreadyDefined.at(span));
         }
         let _ = node.entries.insert(entries);
         Ok(())
     }
-    fn set_range(&mut self, range: Range) -> Result<'a, ()> {
+    fn set_range(&mut self, range: Range, span: Span) -> Result<'a, ()> {
         let Some(node) = self.0.as_mut() else {
-            return Err(ParseError::MissingNode);
+            return Err(ParseErrorKind::MissingNode.at(span));
         };
         if node.range.is_some() {
-            return Err(ParseError::RangeAlreadyDefined);
+            return Err(ParseErrorKind::RangeAlreadyDefined.at(span));
         }
         let _ = node.range.insert(range);
         Ok(())
     }
-    fn pop(&mut self) -> Result<'a, Node<'a>> {
-        self.0.take().ok_or_else(|| todo!("error missing node"))
+    fn pop(&mut self, span: Span) -> Result<'a, Node<'a>> {
+        self.0
+            .take()
+            .ok_or_else(|| ParseErrorKind::EmptySegment.at(span))
     }
 }
 
 impl<'a> Path<'a> {
     pub fn parse(input: &'a str) -> Result<'a, Self> {
-        let mut lexer = Lexer::from(input).peekable();
+        let mut lexer = Lexer::from(input);
+        let path = Self::parse_lexer(&mut lexer)?;
+        match lexer.next_token() {
+            Some(Token { token, span }) => Err(ParseErrorKind::UnexpectedToken(token).at(span)),
+            None => Ok(path),
+        }
+    }
+    /// Parses a path until the end of the input or a `|` separating paths, which is not consumed
+    pub(crate) fn parse_lexer(lexer: &mut Lexer<'a>) -> Result<'a, Self> {
+        let start = lexer.offset();
         let mut nodes = Vec::new();
         let mut node_builder = NodeBuilder::new();
+        let mut last_slash = None;
+        let mut projection = None;
+        // Axis waiting for its node
+        let mut axis: Option<(Axis, Span)> = None;
+        // Annotation waiting for its node
+        let mut annotation: Option<(Cow<'a, str>, Span)> = None;
+        let mut first = true;
         loop {
-            let Some(token) = lexer.next() else {
+            let mut peek = lexer.clone();
+            let Some(Token { token, span }) = peek.next_token() else {
+                *lexer = peek;
                 break;
             };
+            if token == TokenType::Pipe {
+                break;
+            }
+            *lexer = peek;
+            let pending_axis = axis.take();
+            if let Some((_, axis_span)) = &pending_axis {
+                if !matches!(
+                    token,
+                    TokenType::Star
+                        | TokenType::String(_)
+                        | TokenType::Alphanumeric(_)
+                        | TokenType::Glob(_)
+                        | TokenType::Regex(_)
+                        | TokenType::EnterParenthesis
+                ) {
+                    return Err(ParseErrorKind::MissingAxisNode.at(axis_span.clone()));
+                }
+            }
+            if let Some((_, annotation_span)) = &annotation {
+                if !matches!(
+                    token,
+                    TokenType::Star
+                        | TokenType::DoubleStar
+                        | TokenType::DoublePoint
+                        | TokenType::Point
+                        | TokenType::String(_)
+                        | TokenType::Alphanumeric(_)
+                        | TokenType::Glob(_)
+                        | TokenType::Regex(_)
+                ) {
+                    return Err(ParseErrorKind::MissingAnnotatedNode.at(annotation_span.clone()));
+                }
+            }
+            if matches!(token, TokenType::Slash | TokenType::DoubleSlash) {
+                match first {
+                    true => nodes.push(Node::from(NodeKind::Root)),
+                    false => nodes.push(node_builder.pop(span.clone())?),
+                }
+                // `a//b` is a shorthand for `a/**/b`
+                if token == TokenType::DoubleSlash {
+                    nodes.push(Node::from(NodeKind::Anywhere(Descent::default())));
+                }
+                first = false;
+                last_slash = Some(span);
+                continue;
+            }
+            first = false;
+            last_slash = None;
             match token {
-                TokenType::Slash => nodes.push(node_builder.pop()?),
-                TokenType::Star => node_builder.set_node(NodeKind::Any)?,
-                TokenType::DoubleStar => node_builder.set_node(NodeKind::Anywhere)?,
-                TokenType::DoublePoint => node_builder.set_node(NodeKind::Parent)?,
-                TokenType::String(s) => node_builder.set_node(NodeKind::Named(
-                    string::parse_string(s).map_err(|e| e.into_parse_error(s))?,
-                ))?,
+                TokenType::Star => node_builder.set_node(NodeKind::Any, span)?,
+                TokenType::DoubleStar => {
+                    let descent = Self::parse_descent(lexer)?;
+                    node_builder
+                        .set_node(NodeKind::Anywhere(descent), span.start..lexer.offset())?
+                }
+                TokenType::DoublePoint => node_builder.set_node(NodeKind::Parent, span)?,
+                TokenType::Point => node_builder.set_node(NodeKind::Current, span)?,
+                TokenType::String(s) => {
+                    let name = string::parse_string(s)
+                        .map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+                    node_builder.set_node(NodeKind::Named(name), span)?
+                }
                 TokenType::Alphanumeric(s) => {
                     let value = match string::parse_keyword(s) {
                         Ok(value) => value,
-                        Err(ParseError::UnknownKeyword(_)) => {
-                            string::parse_alphanumeric(s).map_err(|e| e.into_parse_error(s))?
-                        }
-                        Err(e) => return Err(e),
+                        Err(ParseErrorKind::UnknownKeyword(_)) => string::parse_alphanumeric(s)
+                            .map_err(|e| e.into_parse_error(s).at(span.clone()))?,
+                        Err(e) => return Err(e.at(span)),
                     };
                     let Value::String(name) = value else {
-                        return Err(ParseError::NotANode);
+                        return Err(ParseErrorKind::NotANode.at(span));
                     };
-                    node_builder.set_node(NodeKind::Named(name))?
+                    node_builder.set_node(NodeKind::Named(name), span)?
+                }
+                TokenType::Glob(s) => {
+                    node_builder.set_node(NodeKind::Glob(Cow::Borrowed(s)), span)?
+                }
+                TokenType::Regex(s) => {
+                    let pattern = Pattern::parse(s).map_err(|e| e.at(span.clone()))?;
+                    node_builder.set_node(NodeKind::Regex(pattern), span)?
+                }
+                TokenType::EnterParenthesis => {
+                    if node_builder.0.is_some() {
+                        return Err(ParseErrorKind::NodeAlreadyDefined.at(span));
+                    }
+                    let parsed = Entries::parse_annotation(lexer, span.start)?;
+                    annotation = Some((parsed, span.start..lexer.offset()));
+                    // The axis applies to the annotated node
+                    axis = pending_axis;
+                    continue;
                 }
                 TokenType::EnterSquareBracket => {
-                    node_builder.set_entries(Entries::parse_lexer(&mut lexer)?)?
+                    let entries = Entries::parse_enclosed(lexer, span.start)?;
+                    node_builder.set_entries(entries, span.start..lexer.offset())?
                 }
                 TokenType::EnterCurlyBracket => {
-                    node_builder.set_range(Self::parse_range(&mut lexer)?)?
+                    let range = Self::parse_range(lexer, span.start)?;
+                    node_builder.set_range(range, span.start..lexer.offset())?
+                }
+                TokenType::Axis(name) => {
+                    if node_builder.0.is_some() {
+                        return Err(ParseErrorKind::NodeAlreadyDefined.at(span));
+                    }
+                    let Some(parsed) = Axis::parse(name) else {
+                        return Err(ParseErrorKind::UnknownAxis(name).at(span));
+                    };
+                    axis = Some((parsed, span));
+                }
+                TokenType::At | TokenType::Dollar if node_builder.0.is_none() => {
+                    if nodes.is_empty() {
+                        return Err(ParseErrorKind::ProjectionWithoutNode.at(span));
+                    }
+                    projection = Some(Self::parse_projection(lexer, token, span)?);
+                    let mut peek = lexer.clone();
+                    match peek.next_token() {
+                        None
+                        | Some(Token {
+                            token: TokenType::Pipe,
+                            ..
+                        }) => break,
+                        Some(Token { span, .. }) => {
+                            return Err(ParseErrorKind::ProjectionNotLast.at(span))
+                        }
+                    }
                 }
-                _ => return Err(ParseError::UnexpectedToken(token)),
+                _ => return Err(ParseErrorKind::UnexpectedToken(token).at(span)),
+            }
+            if let (Some((pending_axis, _)), Some(node)) = (pending_axis, node_builder.0.as_mut()) {
+                let test = std::mem::replace(&mut node.node, NodeKind::Any);
+                node.node = NodeKind::Axis(pending_axis, Box::new(test));
             }
+            if let (Some(_), Some(node)) = (&annotation, node_builder.0.as_mut()) {
+                node.annotation = annotation.take().map(|(annotation, _)| annotation);
+            }
+        }
+        if let Some((_, span)) = axis {
+            return Err(ParseErrorKind::MissingAxisNode.at(span));
+        }
+        if let Some((_, span)) = annotation {
+            return Err(ParseErrorKind::MissingAnnotatedNode.at(span));
+        }
+        if let Some(span) = last_slash {
+            return Err(ParseErrorKind::EmptySegment.at(span));
         }
         if let Some(node) = node_builder.0 {
             nodes.push(node);
         }
-        return Ok(Self { nodes });
+        if nodes.is_empty() {
+            return Err(ParseErrorKind::EmptyQuery.at(start..lexer.offset()));
+        }
+        Ok(Self { nodes, projection })
     }
-    fn parse_range(lexer: &mut impl Iterator<Item = TokenType<'a>>) -> Result<'a, Range> {
+    /// Parses the options of `**` between `<` and `>`, if any: an order then a depth
+    fn parse_descent(lexer: &mut Lexer<'a>) -> Result<'a, Descent> {
+        let mut peek = lexer.clone();
+        let Some(Token {
+            token: TokenType::Less,
+            span: start,
+        }) = peek.next_token()
+        else {
+            return Ok(Descent::default());
+        };
+        *lexer = peek;
+        let mut descent = Descent::default();
+        loop {
+            let mut peek = lexer.clone();
+            match peek.next_token() {
+                Some(Token {
+                    token: TokenType::Greater,
+                    ..
+                }) => {
+                    *lexer = peek;
+                    return Ok(descent);
+                }
+                Some(Token {
+                    token: TokenType::Alphanumeric(name),
+                    span,
+                }) if !name.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') => {
+                    *lexer = peek;
+                    let Some(order) = Order::parse(name) else {
+                        return Err(ParseErrorKind::UnknownOrder(name).at(span));
+                    };
+                    if descent.order.replace(order).is_some() {
+                        return Err(ParseErrorKind::OrderAlreadyDefined.at(span));
+                    }
+                }
+                Some(Token {
+                    token: TokenType::Alphanumeric(_) | TokenType::DoublePoint,
+                    span,
+                }) => {
+                    let depth = Self::parse_bounds(
+                        lexer,
+                        span.start,
+                        TokenType::Greater,
+                        ParseErrorKind::UnterminatedDescent,
+                    )?;
+                    let span = span.start..lexer.offset() - 1;
+                    let (from, to) = depth.indices();
+                    if let Some(negative) = from.into_iter().chain(to).find(|i| *i < 0) {
+                        return Err(ParseErrorKind::NegativeDepth(negative).at(span));
+                    }
+                    if let Range::Stepped(..) = depth {
+                        return Err(ParseErrorKind::SteppedDepth.at(span));
+                    }
+                    descent.depth = Some(depth);
+                    return Ok(descent);
+                }
+                Some(Token { token, span }) => {
+                    return Err(ParseErrorKind::UnexpectedToken(token).at(span))
+                }
+                None => {
+                    return Err(ParseErrorKind::UnterminatedDescent.at(start.start..peek.offset()))
+                }
+            }
+        }
+    }
+    /// Visits the descendants of every `**` without an order of its own in `order`
+    pub fn with_order(mut self, order: Order) -> Self {
+        for node in &mut self.nodes {
+            if let NodeKind::Anywhere(descent) = &mut node.node {
+                descent.order.get_or_insert(order);
+            }
+        }
+        self
+    }
+    /// Parses the entry selected after `@` or `$`
+    fn parse_projection(
+        lexer: &mut Lexer<'a>,
+        token: TokenType<'a>,
+        span: Span,
+    ) -> Result<'a, Projection<'a>> {
+        let selector = match lexer.next_token() {
+            Some(Token {
+                token: TokenType::Star,
+                ..
+            }) => EntrySelector::All,
+            Some(Token {
+                token: TokenType::String(s),
+                span,
+            }) => EntrySelector::Property(
+                string::parse_string(s).map_err(|e| e.into_parse_error(s).at(span))?,
+            ),
+            Some(Token {
+                token: TokenType::Alphanumeric(s),
+                span,
+            }) => match string::parse_alphanumeric(s)
+                .map_err(|e| e.into_parse_error(s).at(span.clone()))?
+            {
+                Value::String(name) => EntrySelector::Property(name),
+                Value::Integer(position) => match position.try_into() {
+                    Ok(position) => EntrySelector::Argument(position),
+                    Err(_) => return Err(ParseErrorKind::NegativeArgumentIndex(position).at(span)),
+                },
+                value => return Err(ParseErrorKind::UnexpectedEntryIdentifier(value).at(span)),
+            },
+            _ => return Err(ParseErrorKind::MissingProjectedEntry(token).at(span)),
+        };
+        Ok(match token {
+            TokenType::Dollar => Projection::Values(selector),
+            _ => Projection::Entries(selector),
+        })
+    }
+    fn parse_range(lexer: &mut Lexer<'a>, start: usize) -> Result<'a, Range> {
+        Self::parse_bounds(
+            lexer,
+            start,
+            TokenType::LeaveCurlyBracket,
+            ParseErrorKind::UnterminatedRange,
+        )
+    }
+    /// Parses the bounds of a range until the `closing` token, `unterminated` being the error when
+    /// it is missing
+    fn parse_bounds(
+        lexer: &mut Lexer<'a>,
+        start: usize,
+        closing: TokenType<'a>,
+        unterminated: ParseErrorKind<'a>,
+    ) -> Result<'a, Range> {
         let mut indices = [None, None];
         let mut has_sep = false;
+        // Explicit end of the range, `..=` or `..<`, along with whether it is excluded
+        let mut end: Option<(Span, bool)> = None;
+        let mut step = None;
         loop {
-            let Some(token) = lexer.next() else {
-                todo!("error missing end of range");
+            let Some(Token { token, span }) = lexer.next_token() else {
+                return Err(unterminated.at(start..lexer.offset()));
             };
             match token {
-                TokenType::Alphanumeric(s) => {
-                    let value = string::parse_alphanumeric(s).map_err(|e| e.into_parse_error(s))?;
-                    let index = match value {
-                        Value::Integer(i) => i,
-                        t => return Err(ParseError::RangeExpectingInteger(t)),
-                    };
+                TokenType::Alphanumeric(s) if step.is_none() => {
+                    let index = Self::parse_index(s, span.clone())?;
                     let i = has_sep as usize;
                     if indices[i].is_some() {
-                        return Err(ParseError::RangeMissingSeparator);
+                        return Err(ParseErrorKind::RangeMissingSeparator.at(span));
                     }
                     indices[i] = Some(index);
                 }
-                TokenType::DoublePoint => {
-                    if has_sep {
-                        return Err(ParseError::UnexpectedToken(TokenType::DoublePoint));
-                    }
+                TokenType::DoublePoint if !has_sep && step.is_none() => {
                     has_sep = true;
+                    let mut peek = lexer.clone();
+                    if let Some(Token {
+                        token: token @ (TokenType::Equal | TokenType::Less),
+                        span: end_span,
+                    }) = peek.next_token()
+                    {
+                        *lexer = peek;
+                        end = Some((span.start..end_span.end, token == TokenType::Less));
+                    }
+                }
+                TokenType::Semicolon if step.is_none() => {
+                    if !has_sep {
+                        return Err(ParseErrorKind::RangeMissingSeparator.at(span));
+                    }
+                    step = Some(Self::parse_step(lexer, span)?);
                 }
-                TokenType::LeaveCurlyBracket => break,
-                _ => return Err(ParseError::UnexpectedToken(token)),
+                _ if token == closing => break,
+                _ => return Err(ParseErrorKind::UnexpectedToken(token).at(span)),
             }
         }
-        match (indices, has_sep) {
-            ([None, None], false) => Err(ParseError::RangeEmpty),
-            ([None, None], true) => Ok(Range::All),
-            ([Some(i), None], false) => Ok(Range::One(i)),
-            ([Some(i), None], true) => Ok(Range::From(i)),
-            ([None, Some(i)], true) => Ok(Range::To(i)),
+        let span = start..lexer.offset();
+        if let (Some((end_span, _)), None) = (&end, indices[1]) {
+            return Err(ParseErrorKind::RangeMissingEnd.at(end_span.clone()));
+        }
+        let exclusive = end.as_ref().is_some_and(|(_, exclusive)| *exclusive);
+        let range = match (indices, has_sep) {
+            ([None, None], false) => return Err(ParseErrorKind::RangeEmpty.at(span)),
+            ([Some(i), None], false) => Range::One(i),
             ([None, Some(_)], false) => unreachable!(),
-
-            ([Some(i), Some(j)], true) => Ok(Range::Both(i, j)),
-            ([Some(_), Some(_)], false) => Err(ParseError::RangeMissingSeparator),
+            ([Some(_), Some(_)], false) => {
+                return Err(ParseErrorKind::RangeMissingSeparator.at(span))
+            }
+            ([None, None], true) => Range::All,
+            ([Some(i), None], true) => Range::From(i),
+            ([None, Some(j)], true) if exclusive => Range::ToExclusive(j),
+            ([None, Some(j)], true) => Range::To(j),
+            ([Some(i), Some(j)], true) if exclusive => Range::BothExclusive(i, j),
+            ([Some(i), Some(j)], true) => Range::Both(i, j),
+        };
+        Ok(match step {
+            Some(step) => Range::Stepped(Box::new(range), step),
+            None => range,
+        })
+    }
+    /// Parses an index of a range, `last` being `-1`
+    fn parse_index(s: &'a str, span: Span) -> Result<'a, i128> {
+        let value =
+            string::parse_alphanumeric(s).map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+        match value {
+            Value::Integer(i) => Ok(i),
+            Value::String(s) if s == "last" => Ok(-1),
+            t => Err(ParseErrorKind::RangeExpectingInteger(t).at(span)),
+        }
+    }
+    /// Parses the step of a range after `;`
+    fn parse_step(lexer: &mut Lexer<'a>, semicolon: Span) -> Result<'a, u64> {
+        let Some(Token {
+            token: TokenType::Alphanumeric(s),
+            span,
+        }) = lexer.next_token()
+        else {
+            return Err(ParseErrorKind::MissingRangeStep.at(semicolon));
+        };
+        let value =
+            string::parse_alphanumeric(s).map_err(|e| e.into_parse_error(s).at(span.clone()))?;
+        match value {
+            Value::Integer(step) if step > 0 => Ok(u64::try_from(step).unwrap_or(u64::MAX)),
+            value => Err(ParseErrorKind::InvalidRangeStep(value).at(span)),
         }
     }
     #[inline]
-    pub fn nodes(&self) -> &[Node] {
+    pub fn nodes(&self) -> &[Node<'_>] {
         &self.nodes
     }
+    #[inline]
+    pub fn projection(&self) -> Option<&Projection<'_>> {
+        self.projection.as_ref()
+    }
+    /// Resolves the path in every way below, the annotated texts being compared with `value_types`
+    /// instead of the default [`crate::resolve::ValueTypes`]
+    #[cfg(feature = "resolve")]
+    pub fn with_types<'t>(
+        &self,
+        value_types: &'t crate::resolve::ValueTypes,
+    ) -> crate::resolve::WithTypes<'_, 't, Self> {
+        crate::resolve::WithTypes::new(self, value_types)
+    }
+    /// Resolves the query, when the path ends with a projection the nodes having the selected
+    /// entries are returned.
+    ///
+    /// Each node is returned once, in document order, unless a `**` of the path has an order:
+    /// the nodes are then in the order they are found
     #[cfg(feature = "resolve")]
     pub fn resolve(self, kdoc: &kdl::KdlDocument) -> Vec<&kdl::KdlNode> {
         crate::resolve::Resolver::resolve(kdoc, self)
     }
+    /// Same as [`Path::resolve`], a node being returned each time the path reaches it, in the
+    /// order they are found. `**/**/pane` returns the nested `pane` nodes several times
+    #[cfg(feature = "resolve")]
+    pub fn resolve_with_duplicates(self, kdoc: &kdl::KdlDocument) -> Vec<&kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_with_duplicates(kdoc)
+    }
+    /// Resolves the query, returning the selected entries or values when the path ends with a
+    /// projection
+    #[cfg(feature = "resolve")]
+    pub fn resolve_matches(self, kdoc: &kdl::KdlDocument) -> Vec<crate::resolve::Match<'_>> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_matches(kdoc)
+    }
+    /// Iterates over the nodes of the query once each, in the order of [`Path::resolve`].
+    ///
+    /// When the path only goes down, or a `**` has an order, each node is resolved only when it
+    /// is requested. Otherwise, as `..`, `/` and the axes can find nodes before the previous
+    /// ones, every node is found before the first one is returned
+    #[cfg(feature = "resolve")]
+    pub fn iter<'k>(
+        &'k self,
+        kdoc: &'k kdl::KdlDocument,
+    ) -> impl Iterator<Item = &'k kdl::KdlNode> + 'k {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .iter(kdoc)
+    }
+    /// First node of [`Path::resolve`], the rest of the document is not searched when the nodes
+    /// are resolved lazily
+    #[cfg(feature = "resolve")]
+    pub fn first<'k>(&self, kdoc: &'k kdl::KdlDocument) -> Option<&'k kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .first(kdoc)
+    }
+    /// Checks whether the query selects at least one node, stopping at the first one
+    #[cfg(feature = "resolve")]
+    pub fn exists(&self, kdoc: &kdl::KdlDocument) -> bool {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .exists(kdoc)
+    }
+    /// Number of nodes selected by the query, without collecting them
+    #[cfg(feature = "resolve")]
+    pub fn count(&self, kdoc: &kdl::KdlDocument) -> usize {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .count(kdoc)
+    }
+    /// Resolves the query from `knode`, relative paths starting at its children.
+    ///
+    /// The ancestors of `knode` are unknown: `..` does not go above it, and a leading `/` selects
+    /// `knode` as the only top-level node
+    #[cfg(feature = "resolve")]
+    pub fn resolve_from(self, knode: &kdl::KdlNode) -> Vec<&kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_from(knode)
+    }
+    /// Resolves the query from each node of `contexts`, relative paths starting at their children.
+    ///
+    /// The contexts found in `kdoc` know their ancestors, so `..` and a leading `/` can go above
+    /// them. The nodes found from several contexts are returned once, as with [`Path::resolve`]
+    #[cfg(feature = "resolve")]
+    pub fn resolve_in<'k>(
+        self,
+        kdoc: &'k kdl::KdlDocument,
+        contexts: &[&'k kdl::KdlNode],
+    ) -> Vec<&'k kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_in(kdoc, contexts)
+    }
+    /// Resolves the query and returns the matching nodes at the root of a new document
+    #[cfg(feature = "resolve")]
+    pub fn resolve_to_document(self, kdoc: &kdl::KdlDocument) -> kdl::KdlDocument {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_to_document(kdoc)
+    }
+    /// Same as [`Path::resolve_to_document`], with the query resolved from `knode` as with
+    /// [`Path::resolve_from`]
+    #[cfg(feature = "resolve")]
+    pub fn resolve_node_to_document(self, knode: &kdl::KdlNode) -> kdl::KdlDocument {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_node_to_document(knode)
+    }
 }
@@ -0,0 +1,123 @@
+use super::{ParseErrorKind, Path, Result};
+use crate::lexer::{Lexer, Token, TokenType};
+use std::fmt::Display;
+
+/// Union of paths separated by `|`, like `layout/**/plugin | plugins/*`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Query<'a> {
+    paths: Vec<Path<'a>>,
+}
+
+impl<'a> Display for Query<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, path) in self.paths.iter().enumerate() {
+            if i != 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{path}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<Path<'a>> for Query<'a> {
+    fn from(path: Path<'a>) -> Self {
+        Self { paths: vec![path] }
+    }
+}
+
+impl<'a> Query<'a> {
+    pub fn parse(input: &'a str) -> Result<'a, Self> {
+        let mut lexer = Lexer::from(input);
+        let mut paths = vec![Path::parse_lexer(&mut lexer)?];
+        while let Some(Token { token, span }) = lexer.next_token() {
+            if token != TokenType::Pipe {
+                return Err(ParseErrorKind::UnexpectedToken(token).at(span));
+            }
+            paths.push(Path::parse_lexer(&mut lexer)?);
+        }
+        Ok(Self { paths })
+    }
+    #[inline]
+    pub fn paths(&self) -> &[Path<'_>] {
+        &self.paths
+    }
+    /// Resolves the query in every way below, the annotated texts being compared with `value_types`
+    /// instead of the default [`crate::resolve::ValueTypes`]
+    #[cfg(feature = "resolve")]
+    pub fn with_types<'t>(
+        &self,
+        value_types: &'t crate::resolve::ValueTypes,
+    ) -> crate::resolve::WithTypes<'_, 't, Self> {
+        crate::resolve::WithTypes::new(self, value_types)
+    }
+    /// Resolves every path, the nodes are returned in document order without duplicates
+    #[cfg(feature = "resolve")]
+    pub fn resolve(self, kdoc: &kdl::KdlDocument) -> Vec<&kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve(kdoc)
+    }
+    /// Same as [`Query::resolve`], returning the entries and values selected by projections
+    #[cfg(feature = "resolve")]
+    pub fn resolve_matches(self, kdoc: &kdl::KdlDocument) -> Vec<crate::resolve::Match<'_>> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_matches(kdoc)
+    }
+    /// Same as [`Path::iter`], for every path of the union. The nodes of several paths are all
+    /// found before the first one is returned, to be sorted
+    #[cfg(feature = "resolve")]
+    pub fn iter<'k>(
+        &'k self,
+        kdoc: &'k kdl::KdlDocument,
+    ) -> impl Iterator<Item = &'k kdl::KdlNode> + 'k {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .iter(kdoc)
+    }
+    /// First node of [`Query::resolve`]
+    #[cfg(feature = "resolve")]
+    pub fn first<'k>(&self, kdoc: &'k kdl::KdlDocument) -> Option<&'k kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .first(kdoc)
+    }
+    /// Checks whether the query selects at least one node
+    #[cfg(feature = "resolve")]
+    pub fn exists(&self, kdoc: &kdl::KdlDocument) -> bool {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .exists(kdoc)
+    }
+    /// Number of nodes selected by the query
+    #[cfg(feature = "resolve")]
+    pub fn count(&self, kdoc: &kdl::KdlDocument) -> usize {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .count(kdoc)
+    }
+    /// Same as [`Path::resolve_from`], for every path of the union
+    #[cfg(feature = "resolve")]
+    pub fn resolve_from(self, knode: &kdl::KdlNode) -> Vec<&kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_from(knode)
+    }
+    /// Same as [`Path::resolve_in`], for every path of the union
+    #[cfg(feature = "resolve")]
+    pub fn resolve_in<'k>(
+        self,
+        kdoc: &'k kdl::KdlDocument,
+        contexts: &[&'k kdl::KdlNode],
+    ) -> Vec<&'k kdl::KdlNode> {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_in(kdoc, contexts)
+    }
+    /// Resolves the query and returns the matching nodes at the root of a new document
+    #[cfg(feature = "resolve")]
+    pub fn resolve_to_document(self, kdoc: &kdl::KdlDocument) -> kdl::KdlDocument {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_to_document(kdoc)
+    }
+    /// Same as [`Query::resolve_to_document`], with the query resolved from `knode` as with
+    /// [`Query::resolve_from`]
+    #[cfg(feature = "resolve")]
+    pub fn resolve_node_to_document(self, knode: &kdl::KdlNode) -> kdl::KdlDocument {
+        self.with_types(&crate::resolve::DEFAULT_VALUE_TYPES)
+            .resolve_node_to_document(knode)
+    }
+}
@@ -1,31 +1,80 @@
-use super::{error::ParseStringError, Value};
-use std::borrow::Cow;
+use super::{error::ParseStringError, ParseErrorKind, Value};
+use crate::lexer::{Lexer, TokenType};
+use std::{borrow::Cow, fmt::Write};
 
 pub type Result<T> = std::result::Result<T, ParseStringError>;
 
 pub fn parse_string<'b>(input: &'b str) -> Result<Cow<'b, str>> {
-    match input.chars().next() {
-        None => return Err(ParseStringError::EmptyString),
-        Some(c) if c != '"' => return Err(ParseStringError::MissingBeginOfString),
-        Some(_) => (),
+    let (contents, raw) = quoted_contents(input)?;
+    if !raw && contents.contains('\\') {
+        Ok(Cow::Owned(unescape_string(&contents)?))
+    } else {
+        Ok(contents)
     }
+}
 
-    match input.chars().last() {
-        None => unreachable!(),
-        Some(c) if c != '"' => return Err(ParseStringError::MissingEndOfString),
-        Some(_) => (),
+/// Returns the contents of a quoted string, without unescaping it
+pub fn string_contents(input: &str) -> Result<Cow<'_, str>> {
+    quoted_contents(input).map(|(contents, _)| contents)
+}
+
+/// Removes the quotes of a text and dedents it if it is a multi-line one, tells whether it is raw
+fn quoted_contents(input: &str) -> Result<(Cow<'_, str>, bool)> {
+    let hashes = input.bytes().take_while(|b| *b == b'#').count();
+    let quoted = &input[hashes..];
+    let quotes = match quoted.chars().next() {
+        None if hashes == 0 => return Err(ParseStringError::EmptyString),
+        Some('"') if quoted.starts_with("\"\"\"") => 3,
+        Some('"') => 1,
+        Some('\'') if hashes == 0 => 1,
+        _ => return Err(ParseStringError::MissingBeginOfString),
+    };
+    let closing = &input[hashes..hashes + quotes];
+    let contents = quoted[quotes..]
+        .strip_suffix(&input[..hashes])
+        .and_then(|contents| contents.strip_suffix(closing))
+        .ok_or(ParseStringError::MissingEndOfString)?;
+    let contents = match quotes {
+        3 => Cow::Owned(dedent(contents)?),
+        _ => Cow::Borrowed(contents),
+    };
+    Ok((contents, hashes > 0))
+}
+
+/// Removes the first and the last lines of a multi-line text, and the whitespaces of the last line
+/// at the start of the other lines
+fn dedent(contents: &str) -> Result<String> {
+    let contents = contents
+        .strip_prefix('\n')
+        .or_else(|| contents.strip_prefix("\r\n"))
+        .ok_or(ParseStringError::MultiLineStart)?;
+    let (lines, indent) = contents.rsplit_once('\n').unwrap_or(("", contents));
+    if !indent.chars().all(char::is_whitespace) {
+        return Err(ParseStringError::MultiLineEnd);
     }
-    if input.chars().any(|c| c == '\\') {
-        Ok(Cow::Owned(unescape_string(&input[1..input.len() - 1])?))
-    } else {
-        Ok(Cow::Borrowed(&input[1..input.len() - 1]))
+    if contents.len() == indent.len() {
+        return Ok(String::new());
     }
+    let lines = lines.split('\n').map(|line| {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match line.chars().all(char::is_whitespace) {
+            true => Ok(""),
+            false => line
+                .strip_prefix(indent)
+                .ok_or(ParseStringError::MultiLineIndent),
+        }
+    });
+    lines
+        .collect::<Result<Vec<_>>>()
+        .map(|lines| lines.join("\n"))
 }
 
 #[derive(Clone, Copy)]
 enum State {
     None,
     Escape,
+    /// After `\` followed by a whitespace, the following whitespaces are skipped
+    Whitespace,
     Ascii(u8, u32),
     EnterUnicode,
     Unicode(u32, u32),
@@ -49,9 +98,15 @@ impl UnescapeString {
         self.state = state;
     }
 }
-pub fn unescape_string<'a>(input: &'a str) -> Result<String> {
+pub fn unescape_string(input: &str) -> Result<String> {
     let mut state = UnescapeString::new(input.len());
     for c in input.chars() {
+        if let State::Whitespace = state.state {
+            if c.is_whitespace() {
+                continue;
+            }
+            state.change_state(State::None);
+        }
         match state.state {
             State::None => {
                 if c == '\\' {
@@ -67,6 +122,12 @@ pub fn unescape_string<'a>(input: &'a str) -> Result<String> {
                     'r' => state.push_char('\r'),
                     // '0' => state.push_char('\0'),
                     '\\' => state.push_char('\\'),
+                    '"' => state.push_char('"'),
+                    '\'' => state.push_char('\''),
+                    'b' => state.push_char('\u{8}'),
+                    'f' => state.push_char('\u{C}'),
+                    's' => state.push_char(' '),
+                    c if c.is_whitespace() => state.change_state(State::Whitespace),
                     'x' => state.change_state(State::Ascii(0, 0)),
                     'u' => state.change_state(State::EnterUnicode),
                     _ => return Err(ParseStringError::UnknownEscape(c)),
@@ -104,6 +165,7 @@ pub fn unescape_string<'a>(input: &'a str) -> Result<String> {
                     }
                 }
             }
+            State::Whitespace => unreachable!("whitespaces are skipped before"),
             State::EnterUnicode => {
                 if c != '{' {
                     return Err(ParseStringError::ExpectedCurlyBracket);
@@ -123,7 +185,7 @@ pub fn parse_alphanumeric<'a>(input: &'a str) -> Result<Value<'a>> {
     }
     use Kind::*;
     let (sign, input2) = match input.chars().next() {
-        None => unreachable!(),
+        None => return Err(ParseStringError::EmptyString),
         Some('-') => (-1, &input[1..]),
         Some('+') => (1, &input[1..]),
         Some(_) => (1, input),
@@ -156,11 +218,71 @@ pub fn parse_alphanumeric<'a>(input: &'a str) -> Result<Value<'a>> {
     };
     Ok(result)
 }
-pub fn parse_keyword<'a>(input: &'a str) -> super::Result<'a, Value<'a>> {
+pub fn parse_keyword<'a>(input: &'a str) -> std::result::Result<Value<'a>, ParseErrorKind<'a>> {
     match input {
         "true" => Ok(Value::Boolean(true)),
         "false" => Ok(Value::Boolean(false)),
         "null" => Ok(Value::Null),
-        _ => Err(super::ParseError::UnknownKeyword(input)),
+        "inf" => Ok(Value::FloatingPoing(f64::INFINITY)),
+        "-inf" => Ok(Value::FloatingPoing(f64::NEG_INFINITY)),
+        "nan" => Ok(Value::FloatingPoing(f64::NAN)),
+        _ => Err(ParseErrorKind::UnknownKeyword(input)),
+    }
+}
+
+/// Writes a text as a bare word when it is read back as the same text, quoted and escaped
+/// otherwise
+pub(crate) fn fmt_text(f: &mut std::fmt::Formatter<'_>, text: &str) -> std::fmt::Result {
+    if is_bare(text) {
+        return f.write_str(text);
+    }
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{C}' => f.write_str("\\f")?,
+            c if c.is_control() => write!(f, "\\u{{{:X}}}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Checks whether a text is a single word which isn't `_`, a keyword nor a number, and which
+/// doesn't end like an operator when `=` follows it
+fn is_bare(text: &str) -> bool {
+    let followed = format!("{text}=");
+    Lexer::from(&followed).next() == Some(TokenType::Alphanumeric(text))
+        && text != "_"
+        && parse_keyword(text).is_err()
+        && matches!(parse_alphanumeric(text), Ok(Value::String(word)) if word == text)
+}
+
+/// Writes a regular expression as a bare word if it is one, or else as a quoted text keeping its
+/// escapes, a raw one when it contains a quote
+pub(crate) fn fmt_pattern(f: &mut std::fmt::Formatter<'_>, pattern: &str) -> std::fmt::Result {
+    if is_bare(pattern) {
+        return f.write_str(pattern);
+    }
+    if !pattern.contains('"') {
+        return write!(f, "\"{pattern}\"");
+    }
+    // One more `#` than after any quote of the pattern, so that it doesn't close the text
+    let hashes = pattern
+        .split('"')
+        .skip(1)
+        .map(|after| after.len() - after.trim_start_matches('#').len())
+        .max()
+        .unwrap_or_default();
+    let hashes = "#".repeat(hashes + 1);
+    match pattern.starts_with("\"\"") {
+        // `#"""` starts a multi-line text
+        true => write!(f, "{hashes}\"\"\"\n{pattern}\n\"\"\"{hashes}"),
+        false => write!(f, "{hashes}\"{pattern}\"{hashes}"),
     }
 }
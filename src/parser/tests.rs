@@ -3,15 +3,16 @@ use std::borrow::Cow;
 use crate::{
     lexer::{Lexer, TokenType},
     parser::{
-        entries::EntryKind, error::ParseStringError, string, Entries, Node, ParseError, Path, Value,
+        entries::EntryKind, error::ParseStringError, string, Axis, Descent, Entries, EntryFilter,
+        EntrySelector, Node, Operator, Order, ParseErrorKind, Path, Projection, Query, Range,
+        Value,
     },
-    util::hashmap,
 };
 
 use super::NodeKind;
 #[test]
 fn parser_strings() {
-    fn test_string(input: &str, output: Result<&str, ParseError>) {
+    fn test_string(input: &str, output: Result<&str, ParseErrorKind>) {
         let mut lexer = Lexer::from(input);
         let token = lexer.next();
         assert_eq!(token, Some(TokenType::String(input)));
@@ -24,28 +25,35 @@ fn parser_strings() {
     test_string("\"hello world\"", Ok("hello world"));
     test_string(
         "\"hello world",
-        Err(ParseError::MalformedString(
+        Err(ParseErrorKind::MalformedString(
             "\"hello world",
             ParseStringError::MissingEndOfString,
         )),
     );
 }
 #[test]
+#[allow(clippy::approx_constant)]
 fn entries() {
     assert_eq!(
         Entries::parse_lexer(&mut Lexer::from(r#"1 2 3"#)),
         Ok(Entries::from(vec![
             EntryKind::Argument {
                 position: 0,
-                value: Some(Value::Integer(1))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(1)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 1,
-                value: Some(Value::Integer(2))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(2)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 2,
-                value: Some(Value::Integer(3))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(3)),
+                annotation: None
             },
         ]))
     );
@@ -54,15 +62,21 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Argument {
                 position: 0,
-                value: Some(Value::Integer(1))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(1)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 1,
-                value: Some(Value::String(Cow::Borrowed("abc")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("abc"))),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 2,
-                value: Some(Value::FloatingPoing(3.14))
+                operator: Operator::Equal,
+                value: Some(Value::FloatingPoing(3.14)),
+                annotation: None
             },
         ]))
     );
@@ -71,11 +85,15 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Property {
                 name: Cow::Borrowed("a"),
-                value: Some(Value::String(Cow::Borrowed("b")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("b"))),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("c"),
-                value: Some(Value::String(Cow::Borrowed("d")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("d"))),
+                annotation: None
             },
         ]))
     );
@@ -84,15 +102,21 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Property {
                 name: Cow::Borrowed("name1"),
-                value: Some(Value::Integer(123))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(123)),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("name2"),
-                value: Some(Value::String(Cow::Borrowed("abc")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("abc"))),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("name3"),
-                value: Some(Value::FloatingPoing(3.14))
+                operator: Operator::Equal,
+                value: Some(Value::FloatingPoing(3.14)),
+                annotation: None
             },
         ]))
     );
@@ -101,15 +125,21 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Argument {
                 position: 1,
-                value: Some(Value::Integer(123))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(123)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 2,
-                value: Some(Value::String(Cow::Borrowed("abc")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("abc"))),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 3,
-                value: Some(Value::FloatingPoing(3.14))
+                operator: Operator::Equal,
+                value: Some(Value::FloatingPoing(3.14)),
+                annotation: None
             },
         ]))
     );
@@ -120,35 +150,51 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Argument {
                 position: 0,
-                value: Some(Value::Integer(1))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(1)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 1,
-                value: Some(Value::Integer(2))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(2)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 2,
-                value: Some(Value::Integer(3))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(3)),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("a"),
-                value: Some(Value::Integer(4))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(4)),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("b"),
-                value: Some(Value::Integer(5))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(5)),
+                annotation: None
             },
             EntryKind::Property {
                 name: Cow::Borrowed("p r o p"),
-                value: Some(Value::String(Cow::Borrowed("v a l u e")))
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("v a l u e"))),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 3,
-                value: Some(Value::Integer(6))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(6)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 10,
-                value: Some(Value::Integer(7))
+                operator: Operator::Equal,
+                value: Some(Value::Integer(7)),
+                annotation: None
             },
         ]))
     );
@@ -157,59 +203,69 @@ fn entries() {
         Ok(Entries::from(vec![
             EntryKind::Argument {
                 position: 0,
-                value: Some(Value::Boolean(true))
+                operator: Operator::Equal,
+                value: Some(Value::Boolean(true)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 1,
-                value: Some(Value::Boolean(false))
+                operator: Operator::Equal,
+                value: Some(Value::Boolean(false)),
+                annotation: None
             },
             EntryKind::Argument {
                 position: 2,
-                value: Some(Value::Null)
+                operator: Operator::Equal,
+                value: Some(Value::Null),
+                annotation: None
             },
         ]))
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"1="#)),
-        Err(ParseError::MissingEntryValue)
+        Entries::parse_lexer(&mut Lexer::from(r#"1="#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryValue)
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"name="#)),
-        Err(ParseError::MissingEntryValue)
+        Entries::parse_lexer(&mut Lexer::from(r#"name="#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryValue)
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"3.1=abc"#)),
-        Err(ParseError::UnexpectedEntryIdentifier(Value::FloatingPoing(
-            3.1
-        )))
+        Entries::parse_lexer(&mut Lexer::from(r#"3.1=abc"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::UnexpectedEntryIdentifier(
+            Value::FloatingPoing(3.1)
+        ))
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"=abc"#)),
-        Err(ParseError::MissingEntryIdentifier)
+        Entries::parse_lexer(&mut Lexer::from(r#"=abc"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryIdentifier)
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"1=abc =cba"#)),
-        Err(ParseError::MissingEntryIdentifier)
+        Entries::parse_lexer(&mut Lexer::from(r#"1=abc =cba"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryIdentifier)
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"name=abc =cba"#)),
-        Err(ParseError::MissingEntryIdentifier)
+        Entries::parse_lexer(&mut Lexer::from(r#"name=abc =cba"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryIdentifier)
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"name=abc [ ]"#)),
-        Err(ParseError::UnexpectedToken(TokenType::EnterSquareBracket))
+        Entries::parse_lexer(&mut Lexer::from(r#"name=abc [ ]"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::UnexpectedToken(
+            TokenType::EnterSquareBracket
+        ))
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"#bla"#)),
-        Err(ParseError::UnknownKeyword("bla"))
+        Entries::parse_lexer(&mut Lexer::from(r#"#bla"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::UnknownKeyword("bla"))
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"#true=2"#)),
-        Err(ParseError::UnexpectedEntryIdentifier(Value::Boolean(true)))
+        Entries::parse_lexer(&mut Lexer::from(r#"#true=2"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::UnexpectedEntryIdentifier(Value::Boolean(
+            true
+        )))
     );
     assert_eq!(
-        Entries::parse_lexer(&mut Lexer::from(r#"#null=2"#)),
-        Err(ParseError::UnexpectedEntryIdentifier(Value::Null))
+        Entries::parse_lexer(&mut Lexer::from(r#"#null=2"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::UnexpectedEntryIdentifier(Value::Null))
     );
 }
 
@@ -222,7 +278,8 @@ fn path_named_one() {
     assert_eq!(
         Path::parse("node_name"),
         Ok(Path {
-            nodes: vec![Node::from(NodeKind::Named(Cow::Borrowed("node_name")))]
+            nodes: vec![Node::from(NodeKind::Named(Cow::Borrowed("node_name")))],
+            projection: None,
         })
     );
 }
@@ -235,7 +292,8 @@ fn path_named_multi() {
             nodes: vec![
                 Node::from(NodeKind::Named(Cow::Borrowed("node1"))),
                 Node::from(NodeKind::Named(Cow::Borrowed("node2"))),
-            ]
+            ],
+            projection: None,
         })
     );
 }
@@ -248,29 +306,71 @@ fn path_named_strings() {
             nodes: vec![
                 Node::from(NodeKind::Named(Cow::Borrowed("node 1"))),
                 Node::from(NodeKind::Named(Cow::Borrowed("node 2"))),
-            ]
+            ],
+            projection: None,
         })
     );
 }
-// #[test]
-// fn path_ident_root() {
-//     assert_eq!(
-//         Path::parse(r#"/node1"#),
-//         Ok(Path {
-//             nodes: vec![Node::from(NodeKind::Root(, Node::from(NodeKind::Named((Cow::Borrowed("node1"))]
-//         })
-//     );
-//     assert_eq!(
-//         Path::parse(r#"/node1/node2"#),
-//         Ok(Path {
-//             nodes: vec![
-//                 Node::from(NodeKind::Root(,
-//                 Node::from(NodeKind::Named((Cow::Borrowed("node1")),
-//                 Node::from(NodeKind::Named((Cow::Borrowed("node2"))
-//             ]
-//         })
-//     );
-// }
+#[test]
+fn path_ident_root() {
+    assert_eq!(
+        Path::parse(r#"/node1"#),
+        Ok(Path {
+            nodes: vec![
+                Node::from(NodeKind::Root),
+                Node::from(NodeKind::Named(Cow::Borrowed("node1")))
+            ],
+            projection: None,
+        })
+    );
+    assert_eq!(
+        Path::parse(r#"/node1/node2"#),
+        Ok(Path {
+            nodes: vec![
+                Node::from(NodeKind::Root),
+                Node::from(NodeKind::Named(Cow::Borrowed("node1"))),
+                Node::from(NodeKind::Named(Cow::Borrowed("node2")))
+            ],
+            projection: None,
+        })
+    );
+    assert_eq!(
+        Path::parse(r#"//node1"#),
+        Ok(Path {
+            nodes: vec![
+                Node::from(NodeKind::Root),
+                Node::from(NodeKind::Anywhere(Descent::default())),
+                Node::from(NodeKind::Named(Cow::Borrowed("node1")))
+            ],
+            projection: None,
+        })
+    );
+    assert_eq!(
+        Path::parse("/node1/node2").unwrap().to_string(),
+        "/node1/node2"
+    );
+    assert_eq!(Path::parse("//node1").unwrap().to_string(), "/**/node1");
+}
+
+#[test]
+fn path_ident_current() {
+    let path = Path::parse(".[focus=#true]").unwrap();
+    assert_eq!(path.nodes()[0].node, NodeKind::Current);
+    assert!(path.nodes()[0].entries.is_some());
+    assert_eq!(path.to_string(), ".[ focus=#true ]");
+    assert_eq!(
+        Path::parse("node1//node2"),
+        Ok(Path {
+            nodes: vec![
+                Node::from(NodeKind::Named(Cow::Borrowed("node1"))),
+                Node::from(NodeKind::Anywhere(Descent::default())),
+                Node::from(NodeKind::Named(Cow::Borrowed("node2")))
+            ],
+            projection: None,
+        })
+    );
+    assert_eq!(Path::parse("./node1/.").unwrap().to_string(), "./node1/.");
+}
 
 #[test]
 fn path_ident_anywhere() {
@@ -278,9 +378,10 @@ fn path_ident_anywhere() {
         Path::parse(r#"**/node1"#),
         Ok(Path {
             nodes: vec![
-                Node::from(NodeKind::Anywhere),
+                Node::from(NodeKind::Anywhere(Descent::default())),
                 Node::from(NodeKind::Named(Cow::Borrowed("node1")))
-            ]
+            ],
+            projection: None,
         })
     );
 }
@@ -293,7 +394,8 @@ fn path_ident_any() {
             nodes: vec![
                 Node::from(NodeKind::Any),
                 Node::from(NodeKind::Named(Cow::Borrowed("node1")))
-            ]
+            ],
+            projection: None,
         })
     );
 }
@@ -305,7 +407,8 @@ fn path_ident_parents() {
             nodes: vec![
                 Node::from(NodeKind::Parent),
                 Node::from(NodeKind::Named(Cow::Borrowed("node1")))
-            ]
+            ],
+            projection: None,
         })
     );
 }
@@ -318,57 +421,73 @@ fn nodes_with_entries() {
             nodes: vec![
                 Node {
                     node: NodeKind::Parent,
+                    annotation: None,
                     entries: Some(Entries::from(vec![EntryKind::Argument {
                         position: 0,
-                        value: Some(Value::Integer(1))
+                        operator: Operator::Equal,
+                        value: Some(Value::Integer(1)),
+                        annotation: None
                     },])),
                     range: None,
                 },
                 Node {
                     node: NodeKind::Named(Cow::Borrowed("node1")),
+                    annotation: None,
                     entries: Some(Entries::from(vec![EntryKind::Argument {
                         position: 0,
-                        value: Some(Value::Integer(2))
+                        operator: Operator::Equal,
+                        value: Some(Value::Integer(2)),
+                        annotation: None
                     },])),
                     range: None,
                 },
                 Node {
                     node: NodeKind::Any,
+                    annotation: None,
                     entries: Some(Entries::from(vec![EntryKind::Argument {
                         position: 0,
-                        value: Some(Value::Integer(3))
+                        operator: Operator::Equal,
+                        value: Some(Value::Integer(3)),
+                        annotation: None
                     },])),
                     range: None,
                 },
                 Node {
-                    node: NodeKind::Anywhere,
+                    node: NodeKind::Anywhere(Descent::default()),
+                    annotation: None,
                     entries: Some(Entries::from(vec![EntryKind::Argument {
                         position: 0,
-                        value: Some(Value::Integer(4))
+                        operator: Operator::Equal,
+                        value: Some(Value::Integer(4)),
+                        annotation: None
                     },])),
                     range: None,
                 },
-            ]
+            ],
+            projection: None,
         })
     );
 
     assert_eq!(
-        Path::parse("node1[1][2]"),
-        Err(ParseError::EntriesAlreadyDefined)
+        Path::parse("node1[1][2]").map_err(|e| e.kind),
+        Err(ParseErrorKind::EntriesAlreadyDefined)
+    );
+    assert_eq!(
+        Path::parse("node1 node2").map_err(|e| e.kind),
+        Err(ParseErrorKind::NodeAlreadyDefined)
     );
     assert_eq!(
-        Path::parse("node1 node2"),
-        Err(ParseError::NodeAlreadyDefined)
+        Path::parse("node1 node2 [1]").map_err(|e| e.kind),
+        Err(ParseErrorKind::NodeAlreadyDefined)
     );
     assert_eq!(
-        Path::parse("node1 node2 [1]"),
-        Err(ParseError::NodeAlreadyDefined)
+        Path::parse("node1 [1] node2").map_err(|e| e.kind),
+        Err(ParseErrorKind::NodeAlreadyDefined)
     );
     assert_eq!(
-        Path::parse("node1 [1] node2"),
-        Err(ParseError::NodeAlreadyDefined)
+        Path::parse("node1/[1]").map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingNode)
     );
-    assert_eq!(Path::parse("node1/[1]"), Err(ParseError::MissingNode));
 }
 #[test]
 fn alphanum() {
@@ -392,6 +511,110 @@ fn alphanum() {
     assert_eq!(parse("-abc"), Ok(String(Cow::Borrowed("-abc"))));
     assert_eq!(parse("a1c"), Ok(String(Cow::Borrowed("a1c"))));
     assert_eq!(parse("-a1c"), Ok(String(Cow::Borrowed("-a1c"))));
+    assert_eq!(parse("+3.5"), Ok(FloatingPoing(3.5)));
+    assert_eq!(parse("+12"), Ok(Integer(12)));
+    assert_eq!(parse("-"), Ok(String(Cow::Borrowed("-"))));
+    assert_eq!(parse("--a"), Ok(String(Cow::Borrowed("--a"))));
+}
+#[test]
+fn special_floats() {
+    let entries = Entries::parse_lexer(&mut Lexer::from("a=#inf b<#-inf c!=#nan")).unwrap();
+    let values: Vec<_> = entries
+        .entries()
+        .iter()
+        .map(|filter| match filter {
+            EntryFilter::Entry(EntryKind::Property { value, .. }) => value.clone().unwrap(),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Value::FloatingPoing(f64::INFINITY),
+            Value::FloatingPoing(f64::NEG_INFINITY),
+            Value::FloatingPoing(f64::NAN),
+        ]
+    );
+    assert_ne!(Value::FloatingPoing(f64::NAN), Value::FloatingPoing(1.0));
+    let path = Path::parse("a[x=#inf y=#-inf z=#nan 0=inf]").unwrap();
+    assert_eq!(path.to_string(), r#"a[ x=#inf y=#-inf z=#nan "inf" ]"#);
+}
+#[test]
+fn strings_display_back() {
+    for (query, displayed) in [
+        (r#""my node""#, r#""my node""#),
+        (r#"a[name="hello world"]"#, r#"a[ name="hello world" ]"#),
+        (r#"a[name~="\.wasm$"]"#, r#"a[ name~="\.wasm$" ]"#),
+        (r##"a[name~=#"say "hi""#]"##, r##"a[ name~=#"say "hi""# ]"##),
+        (r#"a/$"my prop""#, r#"a/$"my prop""#),
+        (r#"a[v<(date)"2024-01-01"]"#, r#"a[ v<(date)"2024-01-01" ]"#),
+        (r##"a[#"C:\dir"#]"##, r#"a[ "C:\\dir" ]"#),
+        (
+            r##"a["x"="_" "1" "#true" "q\"\n"]"##,
+            r##"a[ x="_" "1" "#true" "q\"\n" ]"##,
+        ),
+        (
+            r#""true"/"1"/"a=b"/"a!"/"*"/"..""#,
+            r#""true"/"1"/"a=b"/"a!"/"*"/"..""#,
+        ),
+        ("a[(\"my type\")0=_]", "a[ (\"my type\")_ ]"),
+    ] {
+        let path = Path::parse(query).unwrap();
+        assert_eq!(path.to_string(), displayed);
+        assert_eq!(Path::parse(displayed), Ok(path));
+    }
+}
+#[test]
+fn kdl_identifiers() {
+    let path = Path::parse("zellij:tab-bar/split-direction[env-vars!=a-b]/@font-size").unwrap();
+    let names: Vec<_> = path.nodes().iter().map(|n| n.node.to_string()).collect();
+    assert_eq!(names, ["zellij:tab-bar", "split-direction"]);
+    assert_eq!(
+        path.nodes()[1].entries,
+        Some(Entries::from(vec![EntryKind::Property {
+            name: Cow::Borrowed("env-vars"),
+            operator: Operator::NotEqual,
+            value: Some(Value::String(Cow::Borrowed("a-b"))),
+            annotation: None
+        }]))
+    );
+    assert_eq!(
+        path.projection,
+        Some(Projection::Entries(EntrySelector::Property(Cow::Borrowed(
+            "font-size"
+        ))))
+    );
+    assert_eq!(
+        path.to_string(),
+        "zellij:tab-bar/split-direction[ env-vars!=a-b ]/@font-size"
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from("0=-1 x>=+3.5")),
+        Ok(Entries::from(vec![
+            EntryKind::Argument {
+                position: 0,
+                operator: Operator::Equal,
+                value: Some(Value::Integer(-1)),
+                annotation: None
+            },
+            EntryKind::Property {
+                name: Cow::Borrowed("x"),
+                operator: Operator::GreaterEqual,
+                value: Some(Value::FloatingPoing(3.5)),
+                annotation: None
+            },
+        ]))
+    );
+
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("a[-1=x]"),
+        Err((ParseErrorKind::NegativeArgumentIndex(-1), 2..4))
+    );
+    assert_eq!(
+        error("a/$-2"),
+        Err((ParseErrorKind::NegativeArgumentIndex(-2), 3..5))
+    );
 }
 #[test]
 fn strings() {
@@ -411,10 +634,51 @@ fn strings() {
         parse(r#""aa\u{4F60}\u{597D}\u{4E16}\u{754C}bb""#),
         Ok(Cow::Owned(String::from("aa你好世界bb")))
     );
-    assert_eq!(parse(r#""aa\bbb""#), Err(UnknownEscape('b')));
+    assert_eq!(parse(r#""aa\qbb""#), Err(UnknownEscape('q')));
     assert_eq!(parse(r#""aa\x89bb""#), Err(AsciiNotValid(0x89)));
     assert_eq!(parse(r#""aa\xTRbb""#), Err(NotHexDigit));
     assert_eq!(parse(r#""aa\u{DE01}bb""#), Err(UnicodeNotValid(0xDE01))); // Note: This character doesn't exists in the Unicode chart
+    assert_eq!(
+        parse(r#""\"a\"\b\f\sb""#),
+        Ok(Cow::Owned(String::from("\"a\"\u{8}\u{C} b")))
+    );
+    assert_eq!(
+        parse("\"a \\\n   b\\  c\""),
+        Ok(Cow::Owned(String::from("a bc")))
+    );
+    assert_eq!(parse(r"'it\'s'"), Ok(Cow::Owned(String::from("it's"))));
+    assert_eq!(parse(r"'a\nb'"), Ok(Cow::Owned(String::from("a\nb"))));
+}
+#[test]
+fn raw_and_multi_line_strings() {
+    use string::{parse_string as parse, string_contents};
+    use ParseStringError::*;
+    assert_eq!(parse(r##"#"C:\path\"#"##), Ok(Cow::Borrowed(r"C:\path\")));
+    assert_eq!(parse(r###"##"a"#b"##"###), Ok(Cow::Borrowed(r##"a"#b"##)));
+    assert_eq!(parse(r##"#""#"##), Ok(Cow::Borrowed("")));
+    assert_eq!(parse(r##"#"abc""##), Err(MissingEndOfString));
+    assert_eq!(parse(r##"#'abc'#"##), Err(MissingBeginOfString));
+    assert_eq!(
+        parse("\"\"\"\n    a\n\n      \\\"b\\\"\n    \"\"\""),
+        Ok(Cow::Owned(String::from("a\n\n  \"b\"")))
+    );
+    assert_eq!(
+        parse("#\"\"\"\n  a\\n\n  \"\"\"#"),
+        Ok(Cow::Owned(String::from("a\\n")))
+    );
+    assert_eq!(parse("\"\"\"\n  \"\"\""), Ok(Cow::Owned(String::new())));
+    assert_eq!(parse("\"\"\"a\n\"\"\""), Err(MultiLineStart));
+    assert_eq!(parse("\"\"\"\na\n  b\"\"\""), Err(MultiLineEnd));
+    assert_eq!(parse("\"\"\"\n a\n  \"\"\""), Err(MultiLineIndent));
+    // The escapes are kept for regular expressions
+    assert_eq!(string_contents(r#""a\.b""#), Ok(Cow::Borrowed(r"a\.b")));
+    assert_eq!(string_contents(r##"#"a\.b"#"##), Ok(Cow::Borrowed(r"a\.b")));
+
+    let path = Path::parse(r###"#"C:\dir"#/'it\'s'[path=#"C:\"# name~=#"\d+"#]"###).unwrap();
+    assert_eq!(
+        path.to_string(),
+        r#""C:\\dir"/it's[ path="C:\\" name~="\d+" ]"#
+    );
 }
 
 #[test]
@@ -422,39 +686,801 @@ fn ranges() {
     use super::Range;
     let make_node = |range| Node {
         node: NodeKind::Any,
+        annotation: None,
         entries: None,
         range: Some(range),
     };
-    let parse = |s| Path::parse(s).map(|v| v.nodes);
+    let parse = |s| Path::parse(s).map(|v| v.nodes).map_err(|e| e.kind);
     assert_eq!(parse("*{1}"), Ok(vec![make_node(Range::One(1))]));
     assert_eq!(parse("*{..2}"), Ok(vec![make_node(Range::To(2))]));
     assert_eq!(parse("*{1..}"), Ok(vec![make_node(Range::From(1))]));
     assert_eq!(parse("*{1..2}"), Ok(vec![make_node(Range::Both(1, 2))]));
     assert_eq!(parse("*{..}"), Ok(vec![make_node(Range::All)]));
+    assert_eq!(parse("*{-1}"), Ok(vec![make_node(Range::One(-1))]));
+    assert_eq!(parse("*{last}"), Ok(vec![make_node(Range::One(-1))]));
+    assert_eq!(parse("*{..-2}"), Ok(vec![make_node(Range::To(-2))]));
+    assert_eq!(parse("*{-3..}"), Ok(vec![make_node(Range::From(-3))]));
+    assert_eq!(
+        parse("*{+1..last}"),
+        Ok(vec![make_node(Range::Both(1, -1))])
+    );
+    assert_eq!(Path::parse("*{-3..-1}").unwrap().to_string(), "*{-3..-1}");
+    assert_eq!(parse("*{..=2}"), Ok(vec![make_node(Range::To(2))]));
+    assert_eq!(parse("*{1..=2}"), Ok(vec![make_node(Range::Both(1, 2))]));
+    assert_eq!(parse("*{..<2}"), Ok(vec![make_node(Range::ToExclusive(2))]));
+    assert_eq!(
+        parse("*{1..<-1}"),
+        Ok(vec![make_node(Range::BothExclusive(1, -1))])
+    );
+    assert_eq!(
+        parse("*{0..10;2}"),
+        Ok(vec![make_node(Range::Stepped(
+            Box::new(Range::Both(0, 10)),
+            2
+        ))])
+    );
+    assert_eq!(
+        parse("*{..;3}"),
+        Ok(vec![make_node(Range::Stepped(Box::new(Range::All), 3))])
+    );
+    for range in ["*{1..<3}", "*{..<3;2}", "*{1..;2}"] {
+        assert_eq!(Path::parse(range).unwrap().to_string(), range);
+    }
+    assert_eq!(Path::parse("*{1..=3}").unwrap().to_string(), "*{1..3}");
+    assert_eq!(parse("*{1..=}"), Err(ParseErrorKind::RangeMissingEnd));
+    assert_eq!(parse("*{..<}"), Err(ParseErrorKind::RangeMissingEnd));
+    assert_eq!(parse("*{1;2}"), Err(ParseErrorKind::RangeMissingSeparator));
+    assert_eq!(parse("*{..;}"), Err(ParseErrorKind::MissingRangeStep));
+    assert_eq!(
+        parse("*{..;0}"),
+        Err(ParseErrorKind::InvalidRangeStep(Value::Integer(0)))
+    );
+    assert_eq!(
+        parse("*{..;2;3}"),
+        Err(ParseErrorKind::UnexpectedToken(TokenType::Semicolon))
+    );
+    assert_eq!(
+        parse("*{..;2 3}"),
+        Err(ParseErrorKind::UnexpectedToken(TokenType::Alphanumeric(
+            "3"
+        )))
+    );
     assert_eq!(
         parse("*{abc..}"),
-        Err(ParseError::RangeExpectingInteger(Value::String(
+        Err(ParseErrorKind::RangeExpectingInteger(Value::String(
             Cow::Borrowed("abc")
         )))
     );
     assert_eq!(
         parse("*{..abc}"),
-        Err(ParseError::RangeExpectingInteger(Value::String(
+        Err(ParseErrorKind::RangeExpectingInteger(Value::String(
             Cow::Borrowed("abc")
         )))
     );
     assert_eq!(
         parse("*{1.2..}"),
-        Err(ParseError::RangeExpectingInteger(Value::FloatingPoing(1.2)))
+        Err(ParseErrorKind::RangeExpectingInteger(Value::FloatingPoing(
+            1.2
+        )))
     );
     assert_eq!(
         parse("*{1..2..}"),
-        Err(ParseError::UnexpectedToken(TokenType::DoublePoint))
+        Err(ParseErrorKind::UnexpectedToken(TokenType::DoublePoint))
     );
     assert_eq!(
         parse("*{..2..3}"),
-        Err(ParseError::UnexpectedToken(TokenType::DoublePoint))
+        Err(ParseErrorKind::UnexpectedToken(TokenType::DoublePoint))
+    );
+    assert_eq!(parse("*{1 2}"), Err(ParseErrorKind::RangeMissingSeparator));
+    assert_eq!(parse("*{}"), Err(ParseErrorKind::RangeEmpty));
+}
+
+#[test]
+fn error_spans() {
+    let span = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        span("node1 node2"),
+        Err((ParseErrorKind::NodeAlreadyDefined, 6..11))
+    );
+    assert_eq!(
+        span("node1[1] [2]"),
+        Err((ParseErrorKind::EntriesAlreadyDefined, 9..12))
+    );
+    assert_eq!(span("node1/[1]"), Err((ParseErrorKind::MissingNode, 6..9)));
+    assert_eq!(
+        span("node{1}{..2}"),
+        Err((ParseErrorKind::RangeAlreadyDefined, 7..12))
+    );
+    assert_eq!(span("node{ }"), Err((ParseErrorKind::RangeEmpty, 4..7)));
+    assert_eq!(
+        span("node{1 2}"),
+        Err((ParseErrorKind::RangeMissingSeparator, 7..8))
+    );
+    assert_eq!(
+        span("node{1..x}"),
+        Err((
+            ParseErrorKind::RangeExpectingInteger(Value::String(Cow::Borrowed("x"))),
+            8..9
+        ))
+    );
+    assert_eq!(
+        span("a/b[c=d =e]"),
+        Err((ParseErrorKind::MissingEntryIdentifier, 8..9))
+    );
+    assert_eq!(span("a[c = = d]"), Err((ParseErrorKind::DoubleEqual, 6..7)));
+    assert_eq!(
+        span("a[_=1]"),
+        Err((ParseErrorKind::UndefinedEntryIdentifier, 2..3))
+    );
+    assert_eq!(
+        span("a[#true=1]"),
+        Err((
+            ParseErrorKind::UnexpectedEntryIdentifier(Value::Boolean(true)),
+            2..7
+        ))
+    );
+    assert_eq!(
+        span("a[1 name =]"),
+        Err((ParseErrorKind::MissingEntryValue, 4..10))
+    );
+    assert_eq!(
+        span("a[# bla]"),
+        Err((ParseErrorKind::UnknownKeyword("bla"), 2..7))
+    );
+    assert_eq!(
+        span("a[\"\\q\"]"),
+        Err((
+            ParseErrorKind::MalformedString("\"\\q\"", ParseStringError::UnknownEscape('q')),
+            2..6
+        ))
+    );
+    assert_eq!(
+        span("a/b]"),
+        Err((
+            ParseErrorKind::UnexpectedToken(TokenType::LeaveSquareBracket),
+            3..4
+        ))
+    );
+}
+
+#[test]
+fn error_report() {
+    let query = "layout/pane[split_direction=vertical]/pane{1..x}";
+    let error = Path::parse(query).unwrap_err();
+    assert_eq!(
+        error.report(query).to_string(),
+        "\
+error: expected an integer number, got: x
+  |
+  | layout/pane[split_direction=vertical]/pane{1..x}
+  |                                               ^"
+    );
+    let query = "ünï[a = = b]";
+    let error = Path::parse(query).unwrap_err();
+    assert_eq!(
+        error.report(query).to_string(),
+        "\
+error: double comparison operator in entries
+  |
+  | ünï[a = = b]
+  |         ^"
+    );
+    let query = "a/b[1]\n  [2]";
+    let error = Path::parse(query).unwrap_err();
+    assert_eq!(
+        error.report(query).to_string(),
+        "\
+error: the entries were already defined for this node
+  |
+  |   [2]
+  |   ^^^"
+    );
+}
+
+#[test]
+fn malformed_queries() {
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(error(""), Err((ParseErrorKind::EmptyQuery, 0..0)));
+    assert_eq!(error("   "), Err((ParseErrorKind::EmptyQuery, 0..3)));
+    assert_eq!(error("/"), Err((ParseErrorKind::EmptySegment, 0..1)));
+    assert_eq!(error("a//"), Err((ParseErrorKind::EmptySegment, 1..3)));
+    assert_eq!(error("a///b"), Err((ParseErrorKind::EmptySegment, 3..4)));
+    assert_eq!(error("a/ /b"), Err((ParseErrorKind::EmptySegment, 3..4)));
+    assert_eq!(error("a/b/"), Err((ParseErrorKind::EmptySegment, 3..4)));
+    assert_eq!(
+        error("node{1"),
+        Err((ParseErrorKind::UnterminatedRange, 4..6))
+    );
+    assert_eq!(
+        error("node{"),
+        Err((ParseErrorKind::UnterminatedRange, 4..5))
+    );
+    assert_eq!(
+        error("node[a=1"),
+        Err((ParseErrorKind::UnterminatedEntries, 4..8))
+    );
+    assert_eq!(
+        error("node[#]"),
+        Err((ParseErrorKind::MissingKeyword, 5..6))
+    );
+    assert_eq!(error("node[#"), Err((ParseErrorKind::MissingKeyword, 5..6)));
+    assert_eq!(
+        error("\""),
+        Err((
+            ParseErrorKind::MalformedString("\"", ParseStringError::MissingEndOfString),
+            0..1
+        ))
+    );
+    assert_eq!(
+        Path::parse("a/*[1]/..{2}").map(|p| p.to_string()),
+        Ok(String::from("a/*[ 1 ]/..{2}"))
+    );
+}
+
+proptest::proptest! {
+    #[test]
+    fn parse_never_panics(query in r#"[a-z0-9_ /\[\]{}().*?~^$@=<>!|#"'\\:-]{0,32}"#) {
+        let _ = Path::parse(&query);
+        let _ = Query::parse(&query);
+    }
+    #[test]
+    fn parse_any_string_never_panics(query in ".*") {
+        let _ = Path::parse(&query);
+        let _ = Query::parse(&query);
+    }
+    #[test]
+    fn parsed_queries_display_back(query in r#"((ancestor::|following-sibling::|preceding-sibling::)?([a-z]{1,2}[0-9]?|"([a-z /=]|\\\\|\\"){0,4}"|[a-z]?\*[a-z?]{1,2}|~/[a-z^$.]{0,3}/|\*)|\*\*|\.\.)(\[(([a-z]|[0-9]|"([a-z /=]|\\\\|\\"){0,4}") ?(=|!=|<|<=|>|>=|\^=|\$=|\*=|~=) ?)?([0-9]|(\([a-z]{1,2}[0-9]?\))?"([a-z /=]|\\\\|\\"){0,4}")( !?([a-z0-9](=|<))?[0-9]){0,2}( \(([a-z](=|<))?[0-9]( \| [0-9]){0,2}\))?\])?(\{[0-9]?\.\.[0-9]?\})?(/([a-z]{1,2}[0-9]?|"([a-z /=]|\\\\|\\"){0,4}"|\*|\*\*|\.\.)){0,3}(/(@|\$)([a-z]{1,2}[0-9]?|[0-9]|"([a-z /=]|\\\\|\\"){0,4}"|\*))?"#) {
+        let path = Path::parse(&query).unwrap();
+        let displayed = path.to_string();
+        proptest::prop_assert_eq!(Path::parse(&displayed), Ok(path));
+    }
+}
+
+#[test]
+fn entries_comparisons() {
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from(r#"size>1 0<=2.5 name!="x" 1 >= 3 a<b"#)),
+        Ok(Entries::from(vec![
+            EntryKind::Property {
+                name: Cow::Borrowed("size"),
+                operator: Operator::Greater,
+                value: Some(Value::Integer(1)),
+                annotation: None
+            },
+            EntryKind::Argument {
+                position: 0,
+                operator: Operator::LessEqual,
+                value: Some(Value::FloatingPoing(2.5)),
+                annotation: None
+            },
+            EntryKind::Property {
+                name: Cow::Borrowed("name"),
+                operator: Operator::NotEqual,
+                value: Some(Value::String(Cow::Borrowed("x"))),
+                annotation: None
+            },
+            EntryKind::Argument {
+                position: 1,
+                operator: Operator::GreaterEqual,
+                value: Some(Value::Integer(3)),
+                annotation: None
+            },
+            EntryKind::Property {
+                name: Cow::Borrowed("a"),
+                operator: Operator::Less,
+                value: Some(Value::String(Cow::Borrowed("b"))),
+                annotation: None
+            },
+        ]))
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from(r#"size>_"#)).map_err(|e| (e.kind, e.span)),
+        Err((
+            ParseErrorKind::MissingComparisonValue(Operator::Greater),
+            5..6
+        ))
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from(r#"size>=<1"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::DoubleEqual)
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from(r#"<1"#)).map_err(|e| e.kind),
+        Err(ParseErrorKind::MissingEntryIdentifier)
+    );
+    assert_eq!(
+        Path::parse("pane[size>1 0!=#null]").map(|p| p.to_string()),
+        Ok(String::from("pane[ size>1 0!=#null ]"))
+    );
+}
+
+#[test]
+fn entries_alternatives() {
+    let prop = |name, value| {
+        EntryFilter::Entry(EntryKind::Property {
+            name: Cow::Borrowed(name),
+            operator: Operator::Equal,
+            value: Some(Value::String(Cow::Borrowed(value))),
+            annotation: None,
+        })
+    };
+    let arg = |position, value: Option<i128>| {
+        EntryFilter::Entry(EntryKind::Argument {
+            position,
+            operator: Operator::Equal,
+            value: value.map(Value::Integer),
+            annotation: None,
+        })
+    };
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from("command=helix | command=yazi")),
+        Ok(Entries::from(vec![EntryFilter::Any(vec![
+            Entries::from(vec![prop("command", "helix")]),
+            Entries::from(vec![prop("command", "yazi")]),
+        ])]))
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from("1 (2 3 | 4) 5 !_")),
+        Ok(Entries::from(vec![
+            arg(0, Some(1)),
+            EntryFilter::Any(vec![
+                Entries::from(vec![arg(1, Some(2)), arg(2, Some(3))]),
+                Entries::from(vec![arg(1, Some(4))]),
+            ]),
+            arg(3, Some(5)),
+            EntryFilter::Not(Box::new(arg(4, None))),
+        ]))
+    );
+    assert_eq!(
+        Entries::parse_lexer(&mut Lexer::from("!(a=b | c=d)")),
+        Ok(Entries::from(vec![EntryFilter::Not(Box::new(
+            EntryFilter::Any(vec![
+                Entries::from(vec![prop("a", "b")]),
+                Entries::from(vec![prop("c", "d")]),
+            ])
+        ))]))
+    );
+    assert_eq!(
+        Path::parse("pane[a=b | !c=d (1 | 2)]").map(|p| p.to_string()),
+        Ok(String::from("pane[ ( a=b | !c=d ( 1 | 2 ) ) ]"))
+    );
+    assert_eq!(
+        Path::parse("a[ | b]").map_err(|e| (e.kind, e.span)),
+        Err((ParseErrorKind::EmptyAlternative, 3..4))
+    );
+    assert_eq!(
+        Path::parse("a[b | ]").map_err(|e| e.kind),
+        Err(ParseErrorKind::EmptyAlternative)
+    );
+    assert_eq!(
+        Path::parse("a[(b c]").map_err(|e| (e.kind, e.span)),
+        Err((ParseErrorKind::UnterminatedGroup, 2..7))
+    );
+    assert_eq!(
+        Path::parse("a[b !]").map_err(|e| (e.kind, e.span)),
+        Err((ParseErrorKind::MissingNegatedEntry, 4..5))
+    );
+    assert_eq!(
+        Path::parse("a[b)]").map_err(|e| e.kind),
+        Err(ParseErrorKind::UnexpectedToken(TokenType::LeaveParenthesis))
+    );
+}
+
+#[test]
+fn query_union() {
+    let query = Query::parse("a/b | c[d | e]/f |g").unwrap();
+    assert_eq!(
+        query.paths(),
+        [
+            Path::parse("a/b").unwrap(),
+            Path::parse("c[d | e]/f").unwrap(),
+            Path::parse("g").unwrap(),
+        ]
+    );
+    assert_eq!(query.to_string(), "a/b | c[ ( d | e ) ]/f | g");
+    assert_eq!(
+        Query::parse("a/b"),
+        Ok(Query::from(Path::parse("a/b").unwrap()))
+    );
+    let error = |s| Query::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(error("a | "), Err((ParseErrorKind::EmptyQuery, 3..4)));
+    assert_eq!(error("| a"), Err((ParseErrorKind::EmptyQuery, 0..0)));
+    assert_eq!(error("a || b"), Err((ParseErrorKind::EmptyQuery, 3..3)));
+    assert_eq!(error("a/ | b"), Err((ParseErrorKind::EmptySegment, 1..2)));
+    assert_eq!(
+        Path::parse("a | b").map_err(|e| (e.kind, e.span)),
+        Err((ParseErrorKind::UnexpectedToken(TokenType::Pipe), 2..3))
+    );
+}
+
+#[test]
+fn name_patterns() {
+    let path = Path::parse("env_*/*_x?/a").unwrap();
+    assert_eq!(
+        path.nodes().iter().map(|n| &n.node).collect::<Vec<_>>(),
+        [
+            &NodeKind::Glob(Cow::Borrowed("env_*")),
+            &NodeKind::Glob(Cow::Borrowed("*_x?")),
+            &NodeKind::Named(Cow::Borrowed("a")),
+        ]
+    );
+    let path = Path::parse(r"~/^pane(_\d+)?$/[1]{0}/~/a\/b/").unwrap();
+    let NodeKind::Regex(pattern) = &path.nodes()[0].node else {
+        panic!("expected a regex, got {:?}", path.nodes()[0].node)
+    };
+    assert_eq!(pattern.regex().as_str(), r"^pane(_\d+)?$");
+    assert!(matches!(&path.nodes()[1].node, NodeKind::Regex(r) if r.regex().is_match("a/b")));
+    assert_eq!(path.to_string(), r"~/^pane(_\d+)?$/[ 1 ]{0}/~/a\/b/");
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("a/~/b"),
+        Err((ParseErrorKind::UnterminatedRegex, 2..5))
+    );
+    assert_eq!(
+        error(r"~/b\/"),
+        Err((ParseErrorKind::UnterminatedRegex, 0..5))
+    );
+    assert!(matches!(
+        error("~/(/"),
+        Err((ParseErrorKind::InvalidRegex(_), span)) if span == (0..4)
+    ));
+    assert_eq!(error("a*b*"), Ok(()));
+    assert_eq!(
+        error("a~b"),
+        Err((
+            ParseErrorKind::UnexpectedToken(TokenType::Unknown("~")),
+            1..2
+        ))
+    );
+}
+
+#[test]
+fn entries_string_matches() {
+    let entries = Entries::parse_lexer(&mut Lexer::from(
+        r#"location^="zellij:" 0$=".wasm" name*=helix version^=1.2 location~="https://.*\.wasm""#,
+    ))
+    .unwrap();
+    let operators = entries
+        .entries()
+        .iter()
+        .map(|filter| match filter {
+            EntryFilter::Entry(
+                EntryKind::Argument {
+                    operator, value, ..
+                }
+                | EntryKind::Property {
+                    operator, value, ..
+                },
+            ) => (*operator, value.as_ref().unwrap().to_string()),
+            filter => panic!("unexpected filter {filter:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        operators,
+        [
+            (Operator::StartsWith, String::from("zellij:")),
+            (Operator::EndsWith, String::from(r#"".wasm""#)),
+            (Operator::Contains, String::from("helix")),
+            (Operator::StartsWith, String::from(r#""1.2""#)),
+            (Operator::Matches, String::from(r#""https://.*\.wasm""#)),
+        ]
+    );
+    assert!(matches!(
+        &entries.entries()[4],
+        EntryFilter::Entry(EntryKind::Property {
+            value: Some(Value::Regex(_)),
+            ..
+        })
+    ));
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("a[b^=#true]"),
+        Err((
+            ParseErrorKind::MatchExpectingString(Operator::StartsWith, Value::Boolean(true)),
+            5..10
+        ))
+    );
+    assert_eq!(
+        error(r#"a[b*="x" c$=_]"#),
+        Err((
+            ParseErrorKind::MissingComparisonValue(Operator::EndsWith),
+            12..13
+        ))
+    );
+    assert!(matches!(
+        error(r#"a[b~="(" ]"#),
+        Err((ParseErrorKind::InvalidRegex(_), span)) if span == (5..8)
+    ));
+    assert_eq!(
+        Path::parse("a[name*=helix 1~=x]").map(|p| p.to_string()),
+        Ok(String::from("a[ name*=helix 1~=x ]"))
+    );
+}
+
+#[test]
+fn projections() {
+    let path = Path::parse("**/pane/@size").unwrap();
+    assert_eq!(path.nodes().len(), 2);
+    assert_eq!(
+        path.projection(),
+        Some(&Projection::Entries(EntrySelector::Property(
+            Cow::Borrowed("size")
+        )))
+    );
+    let projection = |s, expected: Option<Projection>| {
+        assert_eq!(Path::parse(s).unwrap().projection(), expected.as_ref())
+    };
+    projection(
+        "pane/$0",
+        Some(Projection::Values(EntrySelector::Argument(0))),
+    );
+    projection(
+        r#"pane/@"my size""#,
+        Some(Projection::Entries(EntrySelector::Property(Cow::Borrowed(
+            "my size",
+        )))),
+    );
+    projection("pane/$*", Some(Projection::Values(EntrySelector::All)));
+    projection("pane", None);
+    assert_eq!(
+        Path::parse("**/pane[size>1]{0}/$size").map(|p| p.to_string()),
+        Ok(String::from("**/pane[ size>1 ]{0}/$size"))
+    );
+    assert_eq!(
+        Query::parse("a/@b | c/$*").map(|q| q.to_string()),
+        Ok(String::from("a/@b | c/$*"))
+    );
+    let error = |s| Query::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("@size"),
+        Err((ParseErrorKind::ProjectionWithoutNode, 0..1))
+    );
+    assert_eq!(
+        error("a/@size/b"),
+        Err((ParseErrorKind::ProjectionNotLast, 7..8))
+    );
+    assert_eq!(
+        error("a/$"),
+        Err((
+            ParseErrorKind::MissingProjectedEntry(TokenType::Dollar),
+            2..3
+        ))
+    );
+    assert_eq!(
+        error("a/@1.5"),
+        Err((
+            ParseErrorKind::UnexpectedEntryIdentifier(Value::FloatingPoing(1.5)),
+            3..6
+        ))
+    );
+    assert_eq!(
+        error("a@b"),
+        Err((ParseErrorKind::UnexpectedToken(TokenType::At), 1..2))
+    );
+}
+
+#[test]
+fn axes() {
+    let path = Path::parse("**/pane[name=helix]/following-sibling::pane{0}").unwrap();
+    assert_eq!(
+        path.nodes()[2].node,
+        NodeKind::Axis(
+            Axis::FollowingSibling,
+            Box::new(NodeKind::Named(Cow::Borrowed("pane")))
+        )
+    );
+    assert_eq!(path.nodes()[2].range, Some(Range::One(0)));
+    assert_eq!(
+        Path::parse("ancestor::*/preceding-sibling::env_*[1]").map(|p| p.to_string()),
+        Ok(String::from("ancestor::*/preceding-sibling::env_*[ 1 ]"))
+    );
+    assert_eq!(
+        Path::parse(r#"ancestor::"a b"/ancestor::~/^a/"#).map(|p| p.to_string()),
+        Ok(String::from(r#"ancestor::"a b"/ancestor::~/^a/"#))
+    );
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("a/sibling::b"),
+        Err((ParseErrorKind::UnknownAxis("sibling"), 2..11))
+    );
+    assert_eq!(
+        error("a/ancestor::/b"),
+        Err((ParseErrorKind::MissingAxisNode, 2..12))
+    );
+    assert_eq!(
+        error("a/ancestor::"),
+        Err((ParseErrorKind::MissingAxisNode, 2..12))
+    );
+    assert_eq!(
+        error("a/ancestor::.."),
+        Err((ParseErrorKind::MissingAxisNode, 2..12))
+    );
+    assert_eq!(
+        error("a/b ancestor::c"),
+        Err((ParseErrorKind::NodeAlreadyDefined, 4..14))
+    );
+}
+
+#[test]
+fn descent_orders() {
+    let anywhere = |order| {
+        NodeKind::Anywhere(Descent {
+            order: Some(order),
+            depth: None,
+        })
+    };
+    for (query, order) in [
+        ("**<pre>", Order::PreOrder),
+        ("**<post>", Order::PostOrder),
+        ("**<breadth>", Order::BreadthFirst),
+        ("**<tree>", Order::FileTree),
+    ] {
+        let path = Path::parse(query).unwrap();
+        assert_eq!(path.nodes()[0].node, anywhere(order));
+        assert_eq!(path.to_string(), query);
+    }
+    assert_eq!(
+        Path::parse("**<post>[a]{1}/node").unwrap().to_string(),
+        "**<post>[ a ]{1}/node"
+    );
+    let path = Path::parse("**<tree>/a/**/b")
+        .unwrap()
+        .with_order(Order::BreadthFirst);
+    assert_eq!(path.to_string(), "**<tree>/a/**<breadth>/b");
+
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("**<up>"),
+        Err((ParseErrorKind::UnknownOrder("up"), 3..5))
+    );
+    assert_eq!(
+        error("**<pre post>"),
+        Err((ParseErrorKind::OrderAlreadyDefined, 7..11))
+    );
+    assert_eq!(
+        error("**<pre"),
+        Err((ParseErrorKind::UnterminatedDescent, 2..6))
+    );
+    assert_eq!(
+        error("**<[a]>"),
+        Err((
+            ParseErrorKind::UnexpectedToken(TokenType::EnterSquareBracket),
+            3..4
+        ))
+    );
+}
+
+#[test]
+fn descent_depths() {
+    let depth = |query| {
+        let path = Path::parse(query).unwrap();
+        let NodeKind::Anywhere(descent) = &path.nodes()[0].node else {
+            panic!("{query} does not start with **");
+        };
+        (descent.order, descent.depth.clone())
+    };
+    assert_eq!(depth("**<2>"), (None, Some(Range::One(2))));
+    assert_eq!(depth("**<1..3>"), (None, Some(Range::Both(1, 3))));
+    assert_eq!(depth("**<..2>"), (None, Some(Range::To(2))));
+    assert_eq!(
+        depth("**<breadth 2..>"),
+        (Some(Order::BreadthFirst), Some(Range::From(2)))
+    );
+    for query in ["**<1..3>/pane", "**<tree 2..>", "**<..>"] {
+        assert_eq!(Path::parse(query).unwrap().to_string(), query);
+    }
+
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(error("**<..;2>"), Err((ParseErrorKind::SteppedDepth, 3..7)));
+    assert_eq!(
+        error("**<-1..>"),
+        Err((ParseErrorKind::NegativeDepth(-1), 3..7))
+    );
+    assert_eq!(
+        error("**<1..3"),
+        Err((ParseErrorKind::UnterminatedDescent, 3..7))
+    );
+    assert_eq!(
+        error("**<1 pre>"),
+        Err((
+            ParseErrorKind::RangeExpectingInteger(Value::String(Cow::Borrowed("pre"))),
+            5..8
+        ))
+    );
+}
+
+#[test]
+fn annotations() {
+    let entries = Entries::parse_lexer(&mut Lexer::from(
+        r#"size=(u8)_ (date)0=_ (u8)255 ("my type")"x" (a | b) (c)"#,
+    ));
+    assert_eq!(
+        entries,
+        Ok(Entries::from(vec![
+            EntryFilter::Entry(EntryKind::Property {
+                name: Cow::Borrowed("size"),
+                operator: Operator::Equal,
+                value: None,
+                annotation: Some(Cow::Borrowed("u8"))
+            }),
+            EntryFilter::Entry(EntryKind::Argument {
+                position: 0,
+                operator: Operator::Equal,
+                value: None,
+                annotation: Some(Cow::Borrowed("date"))
+            }),
+            EntryFilter::Entry(EntryKind::Argument {
+                position: 0,
+                operator: Operator::Equal,
+                value: Some(Value::Integer(255)),
+                annotation: Some(Cow::Borrowed("u8"))
+            }),
+            EntryFilter::Entry(EntryKind::Argument {
+                position: 1,
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("x"))),
+                annotation: Some(Cow::Borrowed("my type"))
+            }),
+            // Not directly followed by a value, these are groups
+            EntryFilter::Any(vec![
+                Entries::from(vec![EntryKind::Argument {
+                    position: 2,
+                    operator: Operator::Equal,
+                    value: Some(Value::String(Cow::Borrowed("a"))),
+                    annotation: None
+                }]),
+                Entries::from(vec![EntryKind::Argument {
+                    position: 2,
+                    operator: Operator::Equal,
+                    value: Some(Value::String(Cow::Borrowed("b"))),
+                    annotation: None
+                }]),
+            ]),
+            EntryFilter::Any(vec![Entries::from(vec![EntryKind::Argument {
+                position: 3,
+                operator: Operator::Equal,
+                value: Some(Value::String(Cow::Borrowed("c"))),
+                annotation: None
+            }])]),
+        ]))
+    );
+
+    let path =
+        Path::parse("(widget)*/ancestor::(layout)pane[(u8)_ size>(f32)1.5]/(x)**/(y).").unwrap();
+    let annotations: Vec<_> = path
+        .nodes()
+        .iter()
+        .map(|n| n.annotation.as_deref())
+        .collect();
+    assert_eq!(
+        annotations,
+        [Some("widget"), Some("layout"), Some("x"), Some("y")]
+    );
+    assert_eq!(
+        path.to_string(),
+        "(widget)*/ancestor::(layout)pane[ (u8)_ size>(f32)1.5 ]/(x)**/(y)."
+    );
+
+    let error = |s| Path::parse(s).map(|_| ()).map_err(|e| (e.kind, e.span));
+    assert_eq!(
+        error("(widget)"),
+        Err((ParseErrorKind::MissingAnnotatedNode, 0..8))
+    );
+    assert_eq!(
+        error("(widget)/a"),
+        Err((ParseErrorKind::MissingAnnotatedNode, 0..8))
+    );
+    assert_eq!(
+        error("a(widget)"),
+        Err((ParseErrorKind::NodeAlreadyDefined, 1..2))
+    );
+    assert_eq!(error("()a"), Err((ParseErrorKind::MissingAnnotation, 0..2)));
+    assert_eq!(
+        error("(a b"),
+        Err((ParseErrorKind::UnterminatedAnnotation, 0..4))
+    );
+    assert_eq!(
+        error("a[(u8)x=(u16)_]"),
+        Err((ParseErrorKind::AnnotationAlreadyDefined, 8..9))
     );
-    assert_eq!(parse("*{1 2}"), Err(ParseError::RangeMissingSeparator));
-    assert_eq!(parse("*{}"), Err(ParseError::RangeEmpty));
 }
@@ -1,27 +1,52 @@
+use super::{string, Pattern};
 use std::{borrow::Cow, fmt::Display};
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, Debug)]
 pub enum Value<'a> {
     /// String
     String(Cow<'a, str>),
     /// Integer
     Integer(i128),
-    /// Floating point
+    /// Floating point, including `#inf`, `#-inf` and `#nan`
     FloatingPoing(f64),
     /// Boolean
     Boolean(bool),
     /// Null
     Null,
+    /// Regular expression, compared with `~=`
+    Regex(Pattern),
 }
 
 impl<'a> Display for Value<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::String(s) => write!(f, "{}", s),
+            Self::String(s) => string::fmt_text(f, s),
             Self::Integer(i) => write!(f, "{}", i),
+            Self::FloatingPoing(fp) if fp.is_nan() => write!(f, "#nan"),
+            Self::FloatingPoing(fp) if fp.is_infinite() => {
+                write!(f, "#{}inf", if fp.is_sign_negative() { "-" } else { "" })
+            }
             Self::FloatingPoing(fp) => write!(f, "{}", fp),
             Self::Boolean(b) => write!(f, "#{}", if *b { "true" } else { "false" }),
             Self::Null => write!(f, "#null"),
+            Self::Regex(r) => string::fmt_pattern(f, r.regex().as_str()),
+        }
+    }
+}
+
+/// Like the derived equality, but `#nan` is equal to itself
+impl<'a> PartialEq for Value<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(s1), Self::String(s2)) => s1 == s2,
+            (Self::Integer(i1), Self::Integer(i2)) => i1 == i2,
+            (Self::FloatingPoing(f1), Self::FloatingPoing(f2)) => {
+                f1 == f2 || (f1.is_nan() && f2.is_nan())
+            }
+            (Self::Boolean(b1), Self::Boolean(b2)) => b1 == b2,
+            (Self::Null, Self::Null) => true,
+            (Self::Regex(r1), Self::Regex(r2)) => r1 == r2,
+            _ => false,
         }
     }
 }
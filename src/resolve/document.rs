@@ -0,0 +1,46 @@
+use kdl::{KdlDocument, KdlNode};
+
+/// Creates a new document having `nodes` at its root.
+///
+/// Nodes keep their original formatting and comments, only the indentation coming from their
+/// original depth is removed. If the result can't be read back (e.g. KDL v1 source), the nodes
+/// are cloned as is.
+pub fn to_document<'k>(nodes: impl IntoIterator<Item = &'k KdlNode>) -> KdlDocument {
+    let nodes: Vec<_> = nodes.into_iter().collect();
+    let mut text = String::new();
+    for node in &nodes {
+        push_dedented(&mut text, &node.to_string());
+    }
+    text.parse().unwrap_or_else(|_| {
+        let mut kdoc = KdlDocument::new();
+        kdoc.nodes_mut().extend(nodes.into_iter().map(|node| {
+            let mut node = node.clone();
+            if let Some(format) = node.format_mut() {
+                if format.terminator.is_empty() {
+                    format.terminator = "\n".into();
+                }
+            }
+            node
+        }));
+        kdoc
+    })
+}
+
+/// Pushes the node's text, without its original indentation, on its own line.
+///
+/// The indentation is the one of the last line, which is either the node itself or the closing
+/// bracket of its children. The blank lines before the node are dropped, the comments before it
+/// are kept.
+fn push_dedented(output: &mut String, node: &str) {
+    let node = node.trim_end();
+    let indent = node
+        .lines()
+        .last()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .unwrap_or_default();
+    let lines = node.lines().skip_while(|line| line.trim().is_empty());
+    for line in lines {
+        output.push_str(line.strip_prefix(indent).unwrap_or(line));
+        output.push('\n');
+    }
+}
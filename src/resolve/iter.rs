@@ -1,5 +1,7 @@
+use super::Context;
+use crate::parser::Order;
 use kdl::KdlNode;
-use std::slice::Iter as SliceIter;
+use std::{collections::VecDeque, iter::Enumerate, slice::Iter as SliceIter};
 
 type BoxIter<'a> = Box<AnywhereIter<'a, SliceIter<'a, KdlNode>>>;
 
@@ -57,3 +59,121 @@ where
     }
 }
 impl<'a, I> AnywhereIterator<'a> for I where I: Iterator<Item = &'a KdlNode> {}
+
+/// Children of a context being visited
+struct Frame<'k> {
+    context: Context<'k>,
+    /// Level of the context below the one the descendants are from
+    depth: usize,
+    children: Enumerate<SliceIter<'k, KdlNode>>,
+    /// Children already listed, waiting for their own children to be listed (file tree order)
+    listed: VecDeque<Context<'k>>,
+}
+
+impl<'k> Frame<'k> {
+    fn new(context: Context<'k>, root: &'k [KdlNode], depth: usize) -> Self {
+        let children = context.children(root).iter().enumerate();
+        Self {
+            context,
+            depth,
+            children,
+            listed: VecDeque::new(),
+        }
+    }
+    /// Next child, along with whether it has children of its own
+    fn next_child(&mut self) -> Option<(Context<'k>, bool)> {
+        let (index, kdl_node) = self.children.next()?;
+        Some((
+            self.context.child(kdl_node, index),
+            kdl_node.children().is_some(),
+        ))
+    }
+}
+
+/// Descendants of a context in the given order, each one along with its ancestors
+pub(crate) struct Descendants<'k> {
+    root: &'k [KdlNode],
+    order: Order,
+    /// Lowest and deepest levels of the descendants, the children being at the level 1
+    levels: (usize, usize),
+    /// A stack for the depth-first orders, a queue for the breadth-first one
+    frames: VecDeque<Frame<'k>>,
+}
+
+impl<'k> Descendants<'k> {
+    pub(crate) fn new(context: Context<'k>, root: &'k [KdlNode], order: Order) -> Self {
+        Self {
+            root,
+            order,
+            levels: (1, usize::MAX),
+            frames: VecDeque::from([Frame::new(context, root, 0)]),
+        }
+    }
+    /// Only selects the descendants between these levels, both included, and does not go deeper
+    pub(crate) fn levels(mut self, levels: (usize, usize)) -> Self {
+        self.levels = levels;
+        self
+    }
+    fn selects(&self, depth: usize) -> bool {
+        (self.levels.0..=self.levels.1).contains(&depth)
+    }
+}
+
+impl<'k> Iterator for Descendants<'k> {
+    type Item = Context<'k>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = match self.order {
+                Order::BreadthFirst => self.frames.front_mut()?,
+                _ => self.frames.back_mut()?,
+            };
+            let depth = frame.depth + 1;
+            let Some((context, has_children)) = frame.next_child() else {
+                match self.order {
+                    Order::PreOrder => {
+                        self.frames.pop_back();
+                    }
+                    // The node comes once its descendants are visited, the context excepted
+                    Order::PostOrder => {
+                        let frame = self.frames.pop_back()?;
+                        if !self.frames.is_empty() && self.selects(frame.depth) {
+                            return Some(frame.context);
+                        }
+                    }
+                    Order::BreadthFirst => {
+                        self.frames.pop_front();
+                    }
+                    // Every child is listed, their own children are listed next
+                    Order::FileTree => match frame.listed.pop_front() {
+                        Some(listed) => {
+                            let listed = Frame::new(listed, self.root, depth);
+                            self.frames.push_back(listed);
+                        }
+                        None => {
+                            self.frames.pop_back();
+                        }
+                    },
+                }
+                continue;
+            };
+            let descend = has_children && depth < self.levels.1;
+            match self.order {
+                Order::FileTree if descend => frame.listed.push_back(context.clone()),
+                Order::FileTree => (),
+                _ if descend => {
+                    let children = Frame::new(context.clone(), self.root, depth);
+                    self.frames.push_back(children);
+                    // The node waits for its descendants
+                    if self.order == Order::PostOrder {
+                        continue;
+                    }
+                }
+                _ => (),
+            }
+            if self.selects(depth) {
+                return Some(context);
+            }
+        }
+    }
+}
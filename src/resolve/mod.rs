@@ -1,106 +1,540 @@
+mod document;
 mod iter;
 mod ops;
 #[cfg(test)]
 mod tests;
-use crate::parser::{Node as QueryNode, NodeKind, Path, RangedIterator};
-use iter::AnywhereIterator;
-use kdl::{KdlDocument, KdlEntry, KdlNode};
+mod typed;
+use crate::parser::{
+    Axis, EntrySelector, Node as QueryNode, NodeKind, Order, Path, Projection, Query,
+    RangedIterator,
+};
+pub use document::to_document;
+use iter::{AnywhereIterator, Descendants};
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+pub(crate) use ops::DEFAULT_VALUE_TYPES;
+pub use ops::{Date, Duration, SemVer, ValueType, ValueTypes};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+pub use typed::WithTypes;
 
-pub(crate) struct Resolver<'k> {
-    current_nodes: Vec<&'k KdlNode>,
-    found_nodes: Vec<&'k KdlNode>,
+/// Result of a query, entries and values are selected by a projection ending the path
+#[derive(Clone, Copy, Debug)]
+pub enum Match<'k> {
+    Node(&'k KdlNode),
+    Entry(&'k KdlEntry),
+    Value(&'k KdlValue),
 }
 
-impl<'k> Resolver<'k> {
+/// A result along with its node and the index of its entry in the node
+type Located<'k> = (&'k KdlNode, Option<usize>, Match<'k>);
+
+/// A node along with its ancestors and its position in its parent's children
+pub(crate) struct Cursor<'k> {
+    node: &'k KdlNode,
+    index: usize,
+    parent: Context<'k>,
+}
+
+/// Where a step of the path starts from, its children are the candidates of the step
+#[derive(Clone)]
+pub(crate) enum Context<'k> {
+    /// Above the top-level nodes, it is never a result
+    Root,
+    /// Above a context node whose ancestors are unknown, it has no children nor parent
+    Detached,
+    Node(Rc<Cursor<'k>>),
+}
+
+impl<'k> Context<'k> {
+    fn child(&self, node: &'k KdlNode, index: usize) -> Self {
+        Context::Node(Rc::new(Cursor {
+            node,
+            index,
+            parent: self.clone(),
+        }))
+    }
+    fn node(&self) -> Option<&'k KdlNode> {
+        match self {
+            Context::Root | Context::Detached => None,
+            Context::Node(cursor) => Some(cursor.node),
+        }
+    }
+    fn children(&self, root: &'k [KdlNode]) -> &'k [KdlNode] {
+        match self {
+            Context::Root => root,
+            Context::Detached => &[],
+            Context::Node(cursor) => cursor
+                .node
+                .children()
+                .map(|kdoc| kdoc.nodes())
+                .unwrap_or_default(),
+        }
+    }
+    fn parent(&self) -> Option<&Self> {
+        match self {
+            Context::Root | Context::Detached => None,
+            Context::Node(cursor) => Some(&cursor.parent),
+        }
+    }
+    fn entries(&self) -> &'k [KdlEntry] {
+        match self {
+            Context::Root | Context::Detached => &[],
+            Context::Node(cursor) => cursor.node.entries(),
+        }
+    }
+}
+
+/// Where a path is resolved from: the top-level nodes, reached by a leading `/`, the contexts
+/// the relative paths start from, and the types comparing the annotated texts
+pub(crate) struct Scope<'k, 't> {
+    root: &'k [KdlNode],
+    contexts: Vec<Context<'k>>,
+    value_types: &'t ValueTypes,
+}
+
+impl<'k> Scope<'k, 'static> {
+    /// Paths start at the top-level nodes
+    pub(crate) fn document(kdl_nodes: &'k [KdlNode]) -> Self {
+        Self {
+            root: kdl_nodes,
+            contexts: vec![Context::Root],
+            value_types: &DEFAULT_VALUE_TYPES,
+        }
+    }
+    /// Paths start at the children of the node, which is the only top-level node
+    pub(crate) fn node(kdl_node: &'k KdlNode) -> Self {
+        Self {
+            root: std::slice::from_ref(kdl_node),
+            contexts: vec![Context::Root.child(kdl_node, 0)],
+            value_types: &DEFAULT_VALUE_TYPES,
+        }
+    }
+    /// Paths start at the children of each node, the ancestors of the nodes found in the
+    /// document are known
+    pub(crate) fn nodes_in(kdl_doc: &'k KdlDocument, kdl_nodes: &[&'k KdlNode]) -> Self {
+        let root = kdl_doc.nodes();
+        let mut located: HashMap<*const KdlNode, Context<'k>> = kdl_nodes
+            .iter()
+            .map(|kdl_node| (std::ptr::from_ref(*kdl_node), Context::Detached))
+            .collect();
+        let mut remaining = located.len();
+        for descendant in Descendants::new(Context::Root, root, Order::PreOrder) {
+            if remaining == 0 {
+                break;
+            }
+            let Some(kdl_node) = descendant.node() else {
+                continue;
+            };
+            if let Some(context) = located.get_mut(&std::ptr::from_ref(kdl_node)) {
+                *context = descendant;
+                remaining -= 1;
+            }
+        }
+        let contexts = kdl_nodes
+            .iter()
+            .map(|kdl_node| match &located[&std::ptr::from_ref(*kdl_node)] {
+                Context::Detached => Context::Detached.child(kdl_node, 0),
+                context => context.clone(),
+            })
+            .collect();
+        Self {
+            root,
+            contexts,
+            value_types: &DEFAULT_VALUE_TYPES,
+        }
+    }
+}
+
+impl<'k, 't> Scope<'k, 't> {
+    /// Same scope, the annotated texts being compared with `value_types`
+    pub(crate) fn with_value_types<'u>(self, value_types: &'u ValueTypes) -> Scope<'k, 'u> {
+        Scope {
+            root: self.root,
+            contexts: self.contexts,
+            value_types,
+        }
+    }
+}
+
+/// Resolves paths lazily, each step of the path being an iterator over the candidates of the
+/// previous one
+#[derive(Clone, Copy)]
+pub(crate) struct Resolver<'k, 't> {
+    root: &'k [KdlNode],
+    value_types: &'t ValueTypes,
+}
+
+/// Boxed iterator borrowing the document for `'k` and the path for `'q`
+type Lazy<'q, T> = Box<dyn Iterator<Item = T> + 'q>;
+
+impl<'k, 't> Resolver<'k, 't> {
     pub(crate) fn resolve<'q>(kdl_doc: &'k KdlDocument, query: Path<'q>) -> Vec<&'k KdlNode> {
-        let mut r = Resolver {
-            current_nodes: Vec::with_capacity(query.nodes().len()),
-            found_nodes: Vec::new(),
+        Self::resolve_nodes(&Scope::document(kdl_doc.nodes()), &query)
+    }
+    /// Resolves the nodes of the path once each, in document order unless a `**` of the path has
+    /// an order. With a projection only the nodes having the selected entries are kept
+    pub(crate) fn resolve_nodes(scope: &Scope<'k, 't>, query: &Path<'_>) -> Vec<&'k KdlNode> {
+        let mut found = Self::resolve_path_nodes(scope, query);
+        if let Some(projection) = query.projection() {
+            found.retain(|kdl_node| project(kdl_node, projection).next().is_some());
+        }
+        found
+    }
+    /// Same as [`Resolver::resolve_nodes`], a node being returned each time the path reaches it,
+    /// in the order they are found
+    pub(crate) fn resolve_nodes_with_duplicates(
+        scope: &Scope<'k, 't>,
+        query: &Path<'_>,
+    ) -> Vec<&'k KdlNode> {
+        let projection = query.projection();
+        Self::iter_path_nodes(scope, query)
+            .filter(|kdl_node| {
+                projection.is_none_or(|projection| project(kdl_node, projection).next().is_some())
+            })
+            .collect()
+    }
+    /// Same as [`Resolver::resolve_nodes`], in the same order. The nodes are found while
+    /// iterating when they are found in this order, otherwise they are all found first
+    pub(crate) fn iter<'q>(scope: &Scope<'k, 't>, query: &'q Path<'_>) -> Lazy<'q, &'k KdlNode>
+    where
+        'k: 'q,
+        't: 'q,
+    {
+        if !found_in_order(scope, query) {
+            return Box::new(Self::resolve_nodes(scope, query).into_iter());
+        }
+        let projection = query.projection();
+        let mut found = HashSet::new();
+        Box::new(Self::iter_path_nodes(scope, query).filter(move |kdl_node| {
+            found.insert(std::ptr::from_ref(*kdl_node))
+                && projection
+                    .is_none_or(|projection| project(kdl_node, projection).next().is_some())
+        }))
+    }
+    /// Same as [`Resolver::iter`] for every path of the union, the nodes of several paths are
+    /// all found first to be sorted
+    pub(crate) fn iter_union<'q>(
+        scope: &Scope<'k, 't>,
+        query: &'q Query<'_>,
+    ) -> Lazy<'q, &'k KdlNode>
+    where
+        'k: 'q,
+        't: 'q,
+    {
+        match query.paths() {
+            [path] => Self::iter(scope, path),
+            _ => Box::new(Self::resolve_union(scope, query).into_iter()),
+        }
+    }
+    pub(crate) fn resolve_matches(scope: &Scope<'k, 't>, query: &Path<'_>) -> Vec<Match<'k>> {
+        Self::resolve_located(scope, query)
+            .into_iter()
+            .map(|(_, _, found)| found)
+            .collect()
+    }
+    /// Resolves every path of the union, in document order and without duplicates
+    pub(crate) fn resolve_union(scope: &Scope<'k, 't>, query: &Query<'_>) -> Vec<&'k KdlNode> {
+        match query.paths() {
+            [path] => Self::resolve_nodes(scope, path),
+            _ => {
+                let mut found = Self::resolve_union_located(scope, query)
+                    .into_iter()
+                    .map(|(kdl_node, _, _)| kdl_node)
+                    .collect::<Vec<_>>();
+                found.dedup_by(|a, b| std::ptr::eq(*a, *b));
+                found
+            }
+        }
+    }
+    /// Same as [`Resolver::resolve_union`], returning the projected entries and values
+    pub(crate) fn resolve_union_matches(
+        scope: &Scope<'k, 't>,
+        query: &Query<'_>,
+    ) -> Vec<Match<'k>> {
+        match query.paths() {
+            [path] => Self::resolve_matches(scope, path),
+            _ => Self::resolve_union_located(scope, query)
+                .into_iter()
+                .map(|(_, _, found)| found)
+                .collect(),
+        }
+    }
+    fn resolve_union_located<'q>(scope: &Scope<'k, 't>, query: &Query<'q>) -> Vec<Located<'k>> {
+        let mut found = query
+            .paths()
+            .iter()
+            .flat_map(|path| Self::resolve_located(scope, path))
+            .collect::<Vec<_>>();
+        let order = document_order(scope.root);
+        // A node comes before its entries
+        found.sort_by_key(|(kdl_node, entry, _)| {
+            (
+                order.get(&std::ptr::from_ref(*kdl_node)),
+                entry.map_or(0, |i| i + 1),
+            )
+        });
+        found.dedup_by(|(a, a_entry, a_found), (b, b_entry, b_found)| {
+            std::ptr::eq(*a, *b)
+                && a_entry == b_entry
+                && std::mem::discriminant(a_found) == std::mem::discriminant(b_found)
+        });
+        found
+    }
+    /// Nodes selected by the steps of the path once each, ignoring its projection
+    fn resolve_path_nodes<'q>(scope: &Scope<'k, 't>, query: &Path<'q>) -> Vec<&'k KdlNode> {
+        let mut seen = HashSet::new();
+        let mut found = Self::iter_path_nodes(scope, query)
+            .filter(|kdl_node| seen.insert(std::ptr::from_ref(*kdl_node)))
+            .collect::<Vec<_>>();
+        if !found_in_order(scope, query) {
+            let order = document_order(scope.root);
+            found.sort_by_key(|kdl_node| order.get(&std::ptr::from_ref(*kdl_node)));
+        }
+        found
+    }
+    /// Nodes selected by the steps of the path, as many times as they are reached, ignoring its
+    /// projection
+    fn iter_path_nodes<'q>(
+        scope: &Scope<'k, 't>,
+        query: &'q Path<'_>,
+    ) -> impl Iterator<Item = &'k KdlNode> + 'q
+    where
+        'k: 'q,
+        't: 'q,
+    {
+        let resolver = Resolver {
+            root: scope.root,
+            value_types: scope.value_types,
+        };
+        let nodes = query.nodes();
+        scope
+            .contexts
+            .clone()
+            .into_iter()
+            .flat_map(move |context| resolver.resolve_query_node(nodes, context))
+    }
+    fn resolve_located<'q>(scope: &Scope<'k, 't>, query: &Path<'q>) -> Vec<Located<'k>> {
+        let found = Self::resolve_path_nodes(scope, query);
+        let Some(projection) = query.projection() else {
+            return found
+                .into_iter()
+                .map(|kdl_node| (kdl_node, None, Match::Node(kdl_node)))
+                .collect();
         };
-        r.resolve_query_node(query.nodes(), kdl_doc.nodes().iter());
-        r.found_nodes
+        found
+            .into_iter()
+            .flat_map(|kdl_node| {
+                project(kdl_node, projection).map(move |(i, entry)| {
+                    let found = match projection {
+                        Projection::Entries(_) => Match::Entry(entry),
+                        Projection::Values(_) => Match::Value(entry.value()),
+                    };
+                    (kdl_node, Some(i), found)
+                })
+            })
+            .collect()
     }
+    /// Resolves the path from the context, the remaining steps being `query`
     fn resolve_query_node<'q>(
-        &mut self,
-        query: &'q [QueryNode],
-        it_nodes: impl Iterator<Item = &'k KdlNode>,
-    ) {
-        let query_node = unsafe { query.first().unwrap_unchecked() };
-        let query_next = &query[1..];
-        let compare_entries = |entries: &[KdlEntry]| {
-            query_node
-                .entries
-                .as_ref()
-                .map(|query_entries| entries == query_entries)
-                .unwrap_or(true)
+        self,
+        query: &'q [QueryNode<'_>],
+        context: Context<'k>,
+    ) -> Lazy<'q, &'k KdlNode>
+    where
+        'k: 'q,
+        't: 'q,
+    {
+        let Some((query_node, query_next)) = query.split_first() else {
+            return Box::new(context.node().into_iter());
         };
-        let node_compare_entries = |node: &&KdlNode| compare_entries(node.entries());
-
-        match &query_node.node {
-            NodeKind::Named(query_name) => {
-                let it = it_nodes
-                    .filter(|kdl_node| {
-                        kdl_node
-                            .name()
-                            .repr()
-                            .map(|node_name| node_name == query_name)
-                            .unwrap_or(false)
-                            && compare_entries(kdl_node.entries())
-                    })
-                    .ranged(query_node.range.as_ref());
-                self.dispatch(query_next, it);
+        let (candidates, query_next) = self.step(query_node, query_next, context);
+        Box::new(
+            candidates.flat_map(move |candidate| self.resolve_query_node(query_next, candidate)),
+        )
+    }
+    /// Candidates of a step of the path from the context, along with the remaining steps, as
+    /// `**` may consume the next one
+    fn step<'q>(
+        self,
+        query_node: &'q QueryNode<'_>,
+        query_next: &'q [QueryNode<'_>],
+        context: Context<'k>,
+    ) -> (Lazy<'q, Context<'k>>, &'q [QueryNode<'q>])
+    where
+        'k: 'q,
+        't: 'q,
+    {
+        let candidates: Lazy<'q, Context<'k>> = match &query_node.node {
+            NodeKind::Named(_) | NodeKind::Glob(_) | NodeKind::Regex(_) | NodeKind::Any => {
+                let children = context.children(self.root).iter().enumerate();
+                Box::new(
+                    children
+                        .filter(move |(_, kdl_node)| query_node.matches(kdl_node, self.value_types))
+                        .ranged(query_node.range.as_ref())
+                        .map(move |(index, kdl_node)| context.child(kdl_node, index)),
+                )
             }
-            NodeKind::Any => self.dispatch(
-                query_next,
-                it_nodes
-                    .filter(node_compare_entries)
+            NodeKind::Axis(axis, _) => Box::new(
+                self.axis(*axis, &context)
+                    .filter(move |candidate| {
+                        candidate
+                            .node()
+                            .is_some_and(|kdl_node| query_node.matches(kdl_node, self.value_types))
+                    })
+                    .ranged(query_node.range.as_ref()),
+            ),
+            NodeKind::Root => Box::new(std::iter::once(Context::Root)),
+            NodeKind::Current => Box::new(
+                std::iter::once(context)
+                    .filter(move |current| query_node.matches_context(current, self.value_types))
                     .ranged(query_node.range.as_ref()),
             ),
-            NodeKind::Anywhere => self.dispatch_itself(query_next, it_nodes.anywhere_nodes()),
-            NodeKind::Parent => {
-                let Some(parent) = self.current_nodes.pop() else {
-                    return;
-                };
-                self.dispatch(
-                    query_next,
-                    std::iter::once(parent)
-                        .filter(node_compare_entries)
-                        .ranged(query_node.range.as_ref()),
-                );
-                self.current_nodes.push(parent);
+            NodeKind::Parent => Box::new(
+                context
+                    .parent()
+                    .cloned()
+                    .into_iter()
+                    .filter(move |parent| query_node.matches_context(parent, self.value_types))
+                    .ranged(query_node.range.as_ref()),
+            ),
+            NodeKind::Anywhere(descent) => {
+                let order = descent.order.unwrap_or_default();
+                let descendants =
+                    Descendants::new(context, self.root, order).levels(descent.levels());
+                match query_next.split_first() {
+                    // `**/<node>` selects the nodes at any depth, ranges apply to all of them
+                    Some((next, query_next)) if descends_to(query_node, next) => {
+                        let it = descendants
+                            .filter(move |descendant| {
+                                descendant.node().is_some_and(|kdl_node| {
+                                    next.matches(kdl_node, self.value_types)
+                                })
+                            })
+                            .ranged(next.range.as_ref());
+                        return (Box::new(it), query_next);
+                    }
+                    _ => Box::new(
+                        descendants
+                            .filter(move |descendant| {
+                                query_node.matches_context(descendant, self.value_types)
+                            })
+                            .ranged(query_node.range.as_ref()),
+                    ),
+                }
             }
+        };
+        (candidates, query_next)
+    }
+    /// Nodes of the axis from the context, the nearest first
+    fn axis(
+        &self,
+        axis: Axis,
+        context: &Context<'k>,
+    ) -> Box<dyn Iterator<Item = Context<'k>> + 'k> {
+        let Context::Node(cursor) = context else {
+            return Box::new(std::iter::empty());
+        };
+        let parent = cursor.parent.clone();
+        let index = cursor.index;
+        let siblings = parent.children(self.root).iter().enumerate();
+        match axis {
+            Axis::Ancestor => Box::new(
+                std::iter::successors(Some(parent), |ancestor| ancestor.parent().cloned())
+                    .filter(|ancestor| matches!(ancestor, Context::Node(_))),
+            ),
+            Axis::FollowingSibling => Box::new(
+                siblings
+                    .skip(index + 1)
+                    .map(move |(i, kdl_node)| parent.child(kdl_node, i)),
+            ),
+            Axis::PrecedingSibling => Box::new(
+                siblings
+                    .take(index)
+                    .rev()
+                    .map(move |(i, kdl_node)| parent.child(kdl_node, i)),
+            ),
         }
     }
-    fn dispatch<'q>(
-        &mut self,
-        query: &'q [QueryNode],
-        it_nodes: impl Iterator<Item = &'k KdlNode>,
-    ) {
-        if query.is_empty() {
-            it_nodes.for_each(|kdl_node| self.found_nodes.push(&kdl_node));
-            return;
+}
+
+/// Checks whether the nodes of the path are found in the order they are returned: going down
+/// from a single context with the default order finds them in document order, and a `**` with
+/// an order returns them in the order they are found
+fn found_in_order(scope: &Scope, query: &Path) -> bool {
+    let has_order = query.nodes().iter().any(|query_node| {
+        matches!(&query_node.node, NodeKind::Anywhere(descent) if descent.order.is_some())
+    });
+    has_order || (scope.contexts.len() == 1 && goes_down(query.nodes()))
+}
+
+/// Checks whether `**` selects the next step at any depth by itself, like in `**/<node>`
+fn descends_to(query_node: &QueryNode, next: &QueryNode) -> bool {
+    query_node.entries.is_none()
+        && query_node.annotation.is_none()
+        && query_node.range.is_none()
+        && matches!(
+            next.node,
+            NodeKind::Named(_) | NodeKind::Glob(_) | NodeKind::Regex(_) | NodeKind::Any
+        )
+}
+
+/// Checks whether the steps only select nodes under their context, in document order. The
+/// steps after a pre-order `**` may select the nodes of a subtree after the ones of a nested
+/// subtree, so it must be the last step, or only select the next one
+fn goes_down(query_nodes: &[QueryNode]) -> bool {
+    let Some((query_node, query_next)) = query_nodes.split_first() else {
+        return true;
+    };
+    match &query_node.node {
+        NodeKind::Named(_)
+        | NodeKind::Glob(_)
+        | NodeKind::Regex(_)
+        | NodeKind::Any
+        | NodeKind::Current => goes_down(query_next),
+        NodeKind::Anywhere(descent) => {
+            descent.order.is_none_or(|o| o == Order::PreOrder)
+                && match query_next {
+                    [] => true,
+                    [next] => descends_to(query_node, next),
+                    _ => false,
+                }
         }
+        NodeKind::Parent | NodeKind::Root | NodeKind::Axis(..) => false,
+    }
+}
 
-        for kdl_node in it_nodes {
-            let Some(kdl_doc) = kdl_node.children() else {
-                self.resolve_query_node(query, std::iter::empty());
-                continue;
-            };
-            self.current_nodes.push(kdl_node);
-            self.resolve_query_node(query, kdl_doc.nodes().iter());
-            let _ = self.current_nodes.pop();
-        }
-    }
-    fn dispatch_itself<'q>(
-        &mut self,
-        query: &'q [QueryNode],
-        it_nodes: impl Iterator<Item = &'k KdlNode>,
-    ) {
-        if query.is_empty() {
-            it_nodes.for_each(|kdl_node| self.found_nodes.push(&kdl_node));
-            return;
-        }
-        let boxed_iter: Box<dyn Iterator<Item = &KdlNode>> = Box::new(it_nodes);
-        self.resolve_query_node(query, boxed_iter);
+/// Position of each node of the document, in document order
+fn document_order(kdl_nodes: &[KdlNode]) -> HashMap<*const KdlNode, usize> {
+    kdl_nodes
+        .iter()
+        .anywhere_nodes()
+        .enumerate()
+        .map(|(i, kdl_node)| (std::ptr::from_ref(kdl_node), i))
+        .collect()
+}
+
+/// Entries of the node selected by the projection, with their index in the node
+fn project<'k>(
+    kdl_node: &'k KdlNode,
+    projection: &Projection<'_>,
+) -> Box<dyn Iterator<Item = (usize, &'k KdlEntry)> + 'k> {
+    let entries = kdl_node.entries().iter().enumerate();
+    match projection.selector() {
+        EntrySelector::All => Box::new(entries),
+        EntrySelector::Argument(position) => Box::new(
+            entries
+                .filter(|(_, entry)| entry.name().is_none())
+                .nth(usize::try_from(*position).unwrap_or(usize::MAX))
+                .into_iter(),
+        ),
+        // The last property wins when a name is repeated
+        EntrySelector::Property(name) => Box::new(
+            entries
+                .rev()
+                .find(|(_, entry)| entry.name().is_some_and(|n| n.value() == name))
+                .into_iter(),
+        ),
     }
 }
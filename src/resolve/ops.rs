@@ -1,49 +1,407 @@
-use crate::parser::{Entries, EntryKind, Value};
-use kdl::{KdlEntry, KdlValue};
+use super::Context;
+use crate::parser::{Entries, EntryFilter, EntryKind, Node, NodeKind, Operator, Value};
+use kdl::{KdlEntry, KdlIdentifier, KdlNode, KdlValue};
+use std::{borrow::Cow, cmp::Ordering, collections::HashMap, fmt, sync::LazyLock};
+
+/// Type of the text values with an annotation, like `(date)"2026-03-01"`, ordering them by what
+/// they mean instead of lexicographically
+pub trait ValueType: Send + Sync {
+    /// Compares two texts of this type, `None` if one of them isn't valid
+    fn compare(&self, left: &str, right: &str) -> Option<Ordering>;
+}
+
+/// Types of the annotated values by their annotation, `date`, `semver` and `duration` by
+/// default. The annotation of the expected value is used first, then the one of the entry
+pub struct ValueTypes {
+    types: HashMap<String, Box<dyn ValueType>>,
+}
+
+/// Types used when none are given
+pub(crate) static DEFAULT_VALUE_TYPES: LazyLock<ValueTypes> = LazyLock::new(ValueTypes::default);
+
+impl ValueTypes {
+    /// No type at all, annotated texts are compared lexicographically
+    pub fn empty() -> Self {
+        Self {
+            types: HashMap::new(),
+        }
+    }
+    /// Adds the type of the values annotated with `annotation`, replacing the previous one
+    pub fn insert(&mut self, annotation: impl Into<String>, value_type: impl ValueType + 'static) {
+        self.types.insert(annotation.into(), Box::new(value_type));
+    }
+    fn get(&self, annotation: &str) -> Option<&dyn ValueType> {
+        self.types.get(annotation).map(|value_type| &**value_type)
+    }
+}
+
+impl Default for ValueTypes {
+    fn default() -> Self {
+        let mut types = Self::empty();
+        types.insert("date", Date);
+        types.insert("semver", SemVer);
+        types.insert("duration", Duration);
+        types
+    }
+}
+
+impl fmt::Debug for ValueTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.types.keys()).finish()
+    }
+}
+
+/// "date" Calendar date like `2026-03-01`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Date;
+
+impl Date {
+    fn parse(text: &str) -> Option<(u32, u32, u32)> {
+        let mut parts = text.splitn(3, '-').map(|part| {
+            (!part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| part.parse::<u32>().ok())
+                .flatten()
+        });
+        let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        (1..=days).contains(&day).then_some((year, month, day))
+    }
+}
+
+impl ValueType for Date {
+    fn compare(&self, left: &str, right: &str) -> Option<Ordering> {
+        Some(Self::parse(left)?.cmp(&Self::parse(right)?))
+    }
+}
+
+/// "semver" Semantic version like `1.4.2` or `2.0.0-rc.1`, build metadata is ignored
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SemVer;
+
+/// Identifier of a pre-release, numbers come before the others
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum PreRelease<'t> {
+    Numeric(u64),
+    Alphanumeric(&'t str),
+}
+
+impl SemVer {
+    fn parse(text: &str) -> Option<([u64; 3], Vec<PreRelease<'_>>)> {
+        let text = text.split_once('+').map_or(text, |(version, _)| version);
+        let (version, pre_release) = match text.split_once('-') {
+            Some((version, pre_release)) => (version, Some(pre_release)),
+            None => (text, None),
+        };
+        let mut numbers = version.split('.').map(|n| n.parse::<u64>().ok());
+        let version = [numbers.next()??, numbers.next()??, numbers.next()??];
+        if numbers.next().is_some() {
+            return None;
+        }
+        let pre_release = pre_release
+            .into_iter()
+            .flat_map(|pre_release| pre_release.split('.'))
+            .map(|identifier| match identifier.parse() {
+                Ok(n) => Some(PreRelease::Numeric(n)),
+                Err(_) if identifier.is_empty() => None,
+                Err(_) => Some(PreRelease::Alphanumeric(identifier)),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some((version, pre_release))
+    }
+}
+
+impl ValueType for SemVer {
+    fn compare(&self, left: &str, right: &str) -> Option<Ordering> {
+        let (left, left_pre) = Self::parse(left)?;
+        let (right, right_pre) = Self::parse(right)?;
+        // A pre-release comes before its release
+        let pre_release = match (left_pre.is_empty(), right_pre.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => left_pre.cmp(&right_pre),
+        };
+        Some(left.cmp(&right).then(pre_release))
+    }
+}
+
+/// "duration" Duration like `30s`, `1h30m` or `250ms`, or ISO 8601 like `PT1H30M` without years
+/// and months as their length varies
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Duration;
+
+impl Duration {
+    /// Length of the duration in seconds
+    fn parse(text: &str) -> Option<f64> {
+        let iso = text.strip_prefix('P');
+        let mut rest = iso.unwrap_or(text).trim();
+        let mut time = false;
+        let mut seconds = 0.0;
+        if rest.is_empty() {
+            return None;
+        }
+        while !rest.is_empty() {
+            if iso.is_some() && !time {
+                if let Some(after) = rest.strip_prefix('T') {
+                    time = true;
+                    rest = after;
+                    continue;
+                }
+            }
+            let number_len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number: f64 = rest[..number_len].parse().ok()?;
+            rest = &rest[number_len..];
+            let unit_len = rest
+                .find(|c: char| !c.is_alphabetic())
+                .unwrap_or(rest.len());
+            let unit = match (iso, time, &rest[..unit_len]) {
+                (None, _, "ns") => 1e-9,
+                (None, _, "us" | "µs") => 1e-6,
+                (None, _, "ms") => 1e-3,
+                (None, _, "s") | (Some(_), true, "S") => 1.0,
+                (None, _, "m") | (Some(_), true, "M") => 60.0,
+                (None, _, "h") | (Some(_), true, "H") => 3600.0,
+                (None, _, "d") | (Some(_), false, "D") => 86400.0,
+                (None, _, "w") | (Some(_), false, "W") => 604800.0,
+                _ => return None,
+            };
+            seconds += number * unit;
+            rest = rest[unit_len..].trim_start();
+        }
+        Some(seconds)
+    }
+}
+
+impl ValueType for Duration {
+    fn compare(&self, left: &str, right: &str) -> Option<Ordering> {
+        Self::parse(left)?.partial_cmp(&Self::parse(right)?)
+    }
+}
 impl<'a> PartialEq<Entries<'a>> for [KdlEntry] {
     fn eq(&self, other: &Entries<'a>) -> bool {
-        let entries = other.entries();
-        for entry in entries {
-            match entry {
-                EntryKind::Argument { position, value } => {
-                    let Ok(pos): Result<usize, _> = (*position).try_into() else {
-                        return false;
-                    };
-                    let Some(arg) = self.iter().filter(|v| v.name().is_none()).nth(pos) else {
-                        return false;
-                    };
-                    if value.as_ref().map(|v| arg.value() != v).unwrap_or(false) {
-                        return false;
-                    };
-                }
-                EntryKind::Property { name, value } => {
-                    let Some(prop) = self
-                        .iter()
-                        .filter_map(|v| v.name().map(|name| (v, name)))
-                        .find(|v| v.1.value() == name)
-                        .map(|v| v.0)
-                    else {
-                        return false;
-                    };
-                    if value.as_ref().map(|v| prop.value() != v).unwrap_or(false) {
-                        return false;
-                    }
-                }
+        other.matches(self, &DEFAULT_VALUE_TYPES)
+    }
+}
+
+impl<'a> Entries<'a> {
+    /// Checks whether the entries of a node match every filter
+    fn matches(&self, entries: &[KdlEntry], value_types: &ValueTypes) -> bool {
+        self.entries()
+            .iter()
+            .all(|filter| filter.matches(entries, value_types))
+    }
+}
+
+impl<'a> EntryFilter<'a> {
+    /// Checks whether the entries of a node match this filter
+    fn matches(&self, entries: &[KdlEntry], value_types: &ValueTypes) -> bool {
+        match self {
+            EntryFilter::Entry(entry) => entry.matches(entries, value_types),
+            EntryFilter::Not(filter) => !filter.matches(entries, value_types),
+            EntryFilter::Any(alternatives) => alternatives
+                .iter()
+                .any(|alternative| alternative.matches(entries, value_types)),
+        }
+    }
+}
+
+impl<'a> EntryKind<'a> {
+    fn matches(&self, entries: &[KdlEntry], value_types: &ValueTypes) -> bool {
+        let (entry, operator, value, annotation) = match self {
+            EntryKind::Argument {
+                position,
+                operator,
+                value,
+                annotation,
+            } => {
+                let Ok(pos): Result<usize, _> = (*position).try_into() else {
+                    return false;
+                };
+                let Some(arg) = entries.iter().filter(|v| v.name().is_none()).nth(pos) else {
+                    return false;
+                };
+                (arg, operator, value, annotation)
+            }
+            EntryKind::Property {
+                name,
+                operator,
+                value,
+                annotation,
+            } => {
+                // The last property wins when a name is repeated
+                let Some(prop) = entries
+                    .iter()
+                    .rev()
+                    .find(|v| v.name().is_some_and(|n| n.value() == name))
+                else {
+                    return false;
+                };
+                (prop, operator, value, annotation)
             }
+        };
+        if !annotation_matches(annotation, entry.ty()) {
+            return false;
+        }
+        let Some(value) = value else {
+            return true;
+        };
+        let value_type = annotation
+            .as_deref()
+            .or(entry.ty().map(|ty| ty.value()))
+            .and_then(|annotation| value_types.get(annotation));
+        operator.compare(entry.value(), value, value_type)
+    }
+}
+
+impl Operator {
+    /// Compares the value of an entry (left) with the expected value (right), texts are compared
+    /// by their type if any
+    pub(crate) fn compare(
+        self,
+        left: &KdlValue,
+        right: &Value,
+        value_type: Option<&dyn ValueType>,
+    ) -> bool {
+        let ordering = || match (value_type, left.as_string(), right) {
+            (Some(value_type), Some(left), Value::String(right)) => value_type.compare(left, right),
+            _ => left.partial_cmp(right),
+        };
+        match (self, left.as_string(), right) {
+            (Operator::Equal, ..) => ordering() == Some(Ordering::Equal),
+            (Operator::NotEqual, ..) => ordering() != Some(Ordering::Equal),
+            (Operator::Less, ..) => ordering() == Some(Ordering::Less),
+            (Operator::LessEqual, ..) => {
+                matches!(ordering(), Some(Ordering::Less | Ordering::Equal))
+            }
+            (Operator::Greater, ..) => ordering() == Some(Ordering::Greater),
+            (Operator::GreaterEqual, ..) => {
+                matches!(ordering(), Some(Ordering::Greater | Ordering::Equal))
+            }
+            (Operator::StartsWith, Some(left), Value::String(right)) => left.starts_with(&**right),
+            (Operator::EndsWith, Some(left), Value::String(right)) => left.ends_with(&**right),
+            (Operator::Contains, Some(left), Value::String(right)) => left.contains(&**right),
+            (Operator::Matches, Some(left), Value::Regex(pattern)) => {
+                pattern.regex().is_match(left)
+            }
+            // String operators never match other values
+            (
+                Operator::StartsWith | Operator::EndsWith | Operator::Contains | Operator::Matches,
+                ..,
+            ) => false,
         }
-        true
     }
 }
 
 impl<'a> PartialEq<Value<'a>> for KdlValue {
     fn eq(&self, other: &Value<'a>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+/// Numbers are compared whatever their type is, strings are compared lexicographically. Booleans
+/// and nulls can only be equal. `#nan` is only equal to itself, and can't be ordered.
+impl<'a> PartialOrd<Value<'a>> for KdlValue {
+    fn partial_cmp(&self, other: &Value<'a>) -> Option<Ordering> {
         match (self, other) {
-            (KdlValue::String(v1), Value::String(v2)) => v1 == v2,
-            (KdlValue::Float(v1), Value::FloatingPoing(v2)) => v1 == v2,
-            (KdlValue::Integer(v1), Value::Integer(v2)) => v1 == v2,
-            (KdlValue::Bool(v1), Value::Boolean(v2)) => v1 == v2,
-            (KdlValue::Null, Value::Null) => true,
-            _ => false,
+            (KdlValue::String(v1), Value::String(v2)) => Some(v1.as_str().cmp(v2)),
+            (KdlValue::Float(v1), Value::FloatingPoing(v2)) if v1.is_nan() && v2.is_nan() => {
+                Some(Ordering::Equal)
+            }
+            (KdlValue::Float(v1), Value::FloatingPoing(v2)) => v1.partial_cmp(v2),
+            (KdlValue::Float(v1), Value::Integer(v2)) => v1.partial_cmp(&(*v2 as f64)),
+            (KdlValue::Integer(v1), Value::FloatingPoing(v2)) => (*v1 as f64).partial_cmp(v2),
+            (KdlValue::Integer(v1), Value::Integer(v2)) => Some(v1.cmp(v2)),
+            (KdlValue::Bool(v1), Value::Boolean(v2)) if v1 == v2 => Some(Ordering::Equal),
+            (KdlValue::Null, Value::Null) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Checks whether the name and the entries of the node match this step
+    pub(crate) fn matches(&self, kdl_node: &KdlNode, value_types: &ValueTypes) -> bool {
+        self.node.matches_name(kdl_node)
+            && annotation_matches(&self.annotation, kdl_node.ty())
+            && self.matches_entries(kdl_node.entries(), value_types)
+    }
+    /// Checks whether the annotation and the entries of the context match this step, the root
+    /// only matches a step without them as it has none to filter on
+    pub(crate) fn matches_context(&self, context: &Context, value_types: &ValueTypes) -> bool {
+        match context.node() {
+            Some(kdl_node) => {
+                annotation_matches(&self.annotation, kdl_node.ty())
+                    && self.matches_entries(context.entries(), value_types)
+            }
+            None => self.entries.is_none() && self.annotation.is_none(),
+        }
+    }
+    fn matches_entries(&self, entries: &[KdlEntry], value_types: &ValueTypes) -> bool {
+        self.entries
+            .as_ref()
+            .is_none_or(|query_entries| query_entries.matches(entries, value_types))
+    }
+}
+
+impl<'a> NodeKind<'a> {
+    /// Checks whether the name of the node matches, `*`, `**` and `..` match any name
+    pub(crate) fn matches_name(&self, kdl_node: &KdlNode) -> bool {
+        match self {
+            NodeKind::Named(name) => kdl_node.name().value() == name,
+            NodeKind::Glob(pattern) => glob_matches(pattern, kdl_node.name().value()),
+            NodeKind::Regex(pattern) => pattern.regex().is_match(kdl_node.name().value()),
+            NodeKind::Axis(_, node) => node.matches_name(kdl_node),
+            NodeKind::Any
+            | NodeKind::Anywhere(_)
+            | NodeKind::Parent
+            | NodeKind::Current
+            | NodeKind::Root => true,
+        }
+    }
+}
+
+/// Checks whether the annotation of a node or a value is the expected one, if any
+fn annotation_matches(expected: &Option<Cow<'_, str>>, ty: Option<&KdlIdentifier>) -> bool {
+    expected
+        .as_ref()
+        .is_none_or(|expected| ty.is_some_and(|ty| ty.value() == expected))
+}
+
+/// Matches a glob pattern where `*` matches any characters and `?` matches exactly one
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and of the name when it was reached
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, from)) => {
+                    p = star + 1;
+                    n = from + 1;
+                    backtrack = Some((star, from + 1));
+                }
+                None => return false,
+            },
         }
     }
+    pattern[p..].iter().all(|c| *c == '*')
 }
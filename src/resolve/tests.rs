@@ -3,8 +3,8 @@ use std::{borrow::Cow, sync::LazyLock};
 
 use crate::{
     lexer::Lexer,
-    parser::{Entries, EntryKind, Path},
-    resolve::Resolver,
+    parser::{Entries, EntryKind, Operator, Order, Path, Query},
+    resolve::{Match, Resolver},
 };
 
 static KDL_DOC: LazyLock<KdlDocument> = LazyLock::new(|| {
@@ -64,7 +64,7 @@ impl PartialEq<TestNode> for KdlNode {
     }
 }
 
-impl<'a> PartialEq<TestNodes> for Vec<&'a KdlNode> {
+impl PartialEq<TestNodes> for Vec<&KdlNode> {
     fn eq(&self, other: &TestNodes) -> bool {
         if self.len() != other.0.len() {
             return false;
@@ -83,10 +83,47 @@ fn entries(input: &'static str) -> Entries<'static> {
     Entries::parse_lexer(&mut lexer).expect("error while parsing entries in tests")
 }
 
+/// Resolves the query in `kdoc`, each node found being described by `describe`
+fn resolve_with<'k, T>(
+    kdoc: &'k KdlDocument,
+    query: &str,
+    describe: impl Fn(&'k KdlNode) -> T,
+) -> Vec<T> {
+    Query::parse(query)
+        .unwrap()
+        .resolve(kdoc)
+        .into_iter()
+        .map(describe)
+        .collect()
+}
+
+fn name(node: &KdlNode) -> &str {
+    node.name().value()
+}
+
+fn text(node: &KdlNode) -> String {
+    node.to_string().trim().to_string()
+}
+
+fn last_value(node: &KdlNode) -> String {
+    node.entries().last().unwrap().value().to_string()
+}
+
+fn first_integer(node: &KdlNode) -> i128 {
+    node.entries()[0].value().as_integer().unwrap()
+}
+
+fn name_and_first_value(node: &KdlNode) -> String {
+    match node.entries().first() {
+        Some(entry) => format!("{} {}", name(node), entry.value()),
+        None => name(node).to_string(),
+    }
+}
+
 #[test]
 fn query_named_node() {
     let query = Path::parse("node2").unwrap();
-    let found = Resolver::resolve(&*KDL_DOC, query);
+    let found = Resolver::resolve(&KDL_DOC, query);
     assert_eq!(
         found,
         TestNodes(vec![
@@ -106,7 +143,7 @@ fn query_any_node() {
     let query = Path::parse("node_children/*").unwrap();
 
     println!("{:#?}", query);
-    let found = Resolver::resolve(&*KDL_DOC, query);
+    let found = Resolver::resolve(&KDL_DOC, query);
     assert_eq!(
         found,
         TestNodes(vec![
@@ -128,7 +165,7 @@ fn query_any_node() {
 #[test]
 fn query_parent_node() {
     let query = Path::parse("node_children/node1/..").unwrap();
-    let found = Resolver::resolve(&*KDL_DOC, query);
+    let found = Resolver::resolve(&KDL_DOC, query);
     assert_eq!(
         found,
         TestNodes(vec![TestNode {
@@ -140,7 +177,8 @@ fn query_parent_node() {
 #[test]
 fn query_parent_node_multi() {
     let query = Path::parse("node_children/*/..").unwrap();
-    let found = Resolver::resolve(&*KDL_DOC, query);
+    assert_eq!(query.clone().resolve(&KDL_DOC).len(), 1);
+    let found = query.resolve_with_duplicates(&KDL_DOC);
     assert_eq!(
         found,
         TestNodes(vec![
@@ -162,33 +200,40 @@ fn query_parent_node_multi() {
 #[test]
 fn query_ranges() {
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/*{1}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/*{1}").unwrap()),
         TestNodes(vec![TestNode {
             name: "node",
             entries: entries("2")
         },])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/..{0}").unwrap()),
+        Resolver::resolve(
+            &KDL_DOC,
+            Path::parse("node_multiple/node{0}/..{0}").unwrap()
+        ),
         TestNodes(vec![TestNode {
             name: "node_multiple",
             entries: Entries::new()
         },])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node2/..{1}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/..{0}").unwrap()),
+        TestNodes(vec![])
+    );
+    assert_eq!(
+        Resolver::resolve(&KDL_DOC, Path::parse("node2/..{1}").unwrap()),
         TestNodes(vec![])
     );
 
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/node{1}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{1}").unwrap()),
         TestNodes(vec![TestNode {
             name: "node",
             entries: entries("2")
         },])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/node{1..}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{1..}").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node",
@@ -209,7 +254,7 @@ fn query_ranges() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/node{..3}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{..3}").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node",
@@ -223,10 +268,31 @@ fn query_ranges() {
                 name: "node",
                 entries: entries("3")
             },
+            TestNode {
+                name: "node",
+                entries: entries("4")
+            },
+        ])
+    );
+    assert_eq!(
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{1..3}").unwrap()),
+        TestNodes(vec![
+            TestNode {
+                name: "node",
+                entries: entries("2")
+            },
+            TestNode {
+                name: "node",
+                entries: entries("3")
+            },
+            TestNode {
+                name: "node",
+                entries: entries("4")
+            },
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/node{1..3}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{1..<3}").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node",
@@ -239,7 +305,7 @@ fn query_ranges() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("node_multiple/node{..}").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("node_multiple/node{..}").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node",
@@ -268,7 +334,7 @@ fn query_ranges() {
 fn query_entries() {
     use crate::parser::Value;
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[_ 2]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[_ 2]").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node2",
@@ -281,7 +347,7 @@ fn query_entries() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[1=2]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[1=2]").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node2",
@@ -295,7 +361,7 @@ fn query_entries() {
     );
     //Note : 3 arguments defined, no matter what are their values
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[_ _ _]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[_ _ _]").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node2",
@@ -312,7 +378,7 @@ fn query_entries() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[hello=world]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[hello=world]").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node_prop",
@@ -329,7 +395,7 @@ fn query_entries() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[hello=world foo=bar]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[hello=world foo=bar]").unwrap()),
         TestNodes(vec![TestNode {
             name: "node_prop",
             entries: entries("hello=world foo=bar")
@@ -337,23 +403,27 @@ fn query_entries() {
     );
     // Just a test to see that the entries parser works well here
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[hello=world 123]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[hello=world 123]").unwrap()),
         TestNodes(vec![TestNode {
             name: "node_prop",
             entries: Entries::from(vec![
                 EntryKind::Property {
                     name: Cow::Borrowed("hello"),
-                    value: Some(Value::String(Cow::Borrowed("world")))
+                    operator: Operator::Equal,
+                    value: Some(Value::String(Cow::Borrowed("world"))),
+                    annotation: None
                 },
                 EntryKind::Argument {
                     position: 0,
-                    value: Some(Value::Integer(123))
+                    operator: Operator::Equal,
+                    value: Some(Value::Integer(123)),
+                    annotation: None
                 }
             ])
         },])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("*[hello=world foo=bar]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("*[hello=world foo=bar]").unwrap()),
         TestNodes(vec![TestNode {
             name: "node_prop",
             entries: entries("hello=world foo=bar")
@@ -361,7 +431,7 @@ fn query_entries() {
     );
     assert_eq!(
         Resolver::resolve(
-            &*KDL_DOC,
+            &KDL_DOC,
             Path::parse("article/contents/section/paragraph[\"This is the first paragraph\"]")
                 .unwrap()
         ),
@@ -372,7 +442,7 @@ fn query_entries() {
     );
     assert_eq!(
         Resolver::resolve(
-            &*KDL_DOC,
+            &KDL_DOC,
             Path::parse("*/*/*/*[\"This is the third paragraph\"]").unwrap()
         ),
         TestNodes(vec![TestNode {
@@ -382,7 +452,7 @@ fn query_entries() {
     );
     assert_eq!(
         Resolver::resolve(
-            &*KDL_DOC,
+            &KDL_DOC,
             Path::parse("article/contents/section[\"Second section\"]/*").unwrap()
         ),
         TestNodes(vec![
@@ -400,7 +470,7 @@ fn query_entries() {
 #[test]
 fn query_anywhere() {
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("article/**").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("article/**").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "contents",
@@ -437,7 +507,7 @@ fn query_anywhere() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("article/**/paragraph").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("article/**/paragraph").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "paragraph",
@@ -458,7 +528,7 @@ fn query_anywhere() {
         ])
     );
     assert_eq!(
-        Resolver::resolve(&*KDL_DOC, Path::parse("**/*[2]").unwrap()),
+        Resolver::resolve(&KDL_DOC, Path::parse("**/*[2]").unwrap()),
         TestNodes(vec![
             TestNode {
                 name: "node2",
@@ -471,3 +541,901 @@ fn query_anywhere() {
         ])
     );
 }
+#[test]
+fn query_to_document() {
+    let query = Path::parse("article/contents/section[\"Second section\"]").unwrap();
+    assert_eq!(
+        query.resolve_to_document(&KDL_DOC).to_string(),
+        r#"section "Second section" {
+    paragraph "This is the third paragraph"
+    paragraph "This is the forth paragraph"
+}
+"#
+    );
+    let query = Path::parse("node_multiple/node{1..3}").unwrap();
+    assert_eq!(
+        query.resolve_to_document(&KDL_DOC).to_string(),
+        "node 2\nnode 3\nnode 4\n"
+    );
+
+    let commented: KdlDocument = r#"
+        parent {
+            // first comment
+            child 1 /- 2 {
+                grandchild
+            }
+            /- child 3
+            child 4; child 5
+        }
+        "#
+    .parse()
+    .unwrap();
+    let kdoc = Path::parse("child")
+        .unwrap()
+        .resolve_node_to_document(&commented.nodes()[0]);
+    assert_eq!(
+        kdoc.to_string(),
+        r#"// first comment
+child 1 /- 2 {
+    grandchild
+}
+/- child 3
+child 4;
+child 5
+"#
+    );
+    assert_eq!(kdoc.nodes().len(), 3);
+    assert!(Path::parse("nothing")
+        .unwrap()
+        .resolve_to_document(&KDL_DOC)
+        .nodes()
+        .is_empty());
+}
+
+proptest::proptest! {
+    #[test]
+    fn resolve_never_panics(query in r#"[a-z0-9_ /\[\]{}().*?~^$=<>!|#"\-@;'\\:]{0,32}"#) {
+        if let Ok(path) = Path::parse(&query) {
+            let _ = path.resolve(&KDL_DOC);
+        }
+    }
+}
+
+#[test]
+fn query_comparisons() {
+    let kdoc: KdlDocument = r#"
+        pane size=1 name=a
+        pane size=2.5 name=b
+        pane size=3 name=c
+        pane name=d
+        job 0 retries=0
+        job 1.5 retries=2
+        job "x" retries=#null
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, last_value);
+    assert_eq!(names("pane[size>1]"), ["b", "c"]);
+    assert_eq!(names("pane[size>=1]"), ["a", "b", "c"]);
+    assert_eq!(names("pane[size<2.5]"), ["a"]);
+    assert_eq!(names("pane[size<=2.5]"), ["a", "b"]);
+    assert_eq!(names("pane[size=3.0]"), ["c"]);
+    assert_eq!(names("pane[size!=3]"), ["a", "b"]);
+    assert_eq!(names("pane[name>b]"), ["c", "d"]);
+    assert_eq!(names("pane[name<=b size>1]"), ["b"]);
+    assert_eq!(names("job[retries!=0]"), ["2", "#null"]);
+    assert_eq!(names("job[0>=1]"), ["2"]);
+    assert_eq!(names("job[0<1]"), ["0"]);
+    assert_eq!(names("job[0>a]"), ["#null"]);
+}
+
+#[test]
+fn query_alternatives() {
+    let kdoc: KdlDocument = r#"
+        pane command=helix name=a
+        pane command=yazi name=b
+        pane command=bash name=c
+        pane 1 2 name=d
+        pane 1 name=e
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, last_value);
+    assert_eq!(names("pane[command=helix | command=yazi]"), ["a", "b"]);
+    assert_eq!(names("pane[!command=bash]"), ["a", "b", "d", "e"]);
+    assert_eq!(names("pane[!command=_]"), ["d", "e"]);
+    assert_eq!(
+        names("pane[!(command=helix | command=yazi)]"),
+        ["c", "d", "e"]
+    );
+    assert_eq!(names("pane[1 !_]"), ["e"]);
+    assert_eq!(names("pane[(command=bash | 1) name>c]"), ["d", "e"]);
+}
+
+#[test]
+fn query_union() {
+    let names = |query| resolve_with(&KDL_DOC, query, text);
+    assert_eq!(
+        names("node_children/node3 | node_multiple/node{1} | node_children/node1"),
+        ["node1 1", "node3 3", "node 2"]
+    );
+    assert_eq!(
+        names("node_multiple/node{..1} | node_multiple/node{1..<2} | node2[1]"),
+        ["node2 1 2 3", "node 1", "node 2"]
+    );
+    assert_eq!(names("unknown | node1"), ["node1"]);
+    let node = &KDL_DOC.nodes()[8];
+    assert_eq!(
+        Query::parse("node3 | node1")
+            .unwrap()
+            .resolve_node_to_document(node)
+            .to_string(),
+        "node1 1\nnode3 3\n"
+    );
+}
+
+#[test]
+fn query_name_patterns() {
+    let kdoc: KdlDocument = r#"
+        env_home
+        env_path
+        envy
+        pane
+        pane_1
+        pane_12
+        pane_x
+        "my pane"
+        "éa"
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("env_*"), ["env_home", "env_path"]);
+    assert_eq!(names("env*"), ["env_home", "env_path", "envy"]);
+    assert_eq!(names("*_*h"), ["env_path"]);
+    assert_eq!(names("pane_?"), ["pane_1", "pane_x"]);
+    assert_eq!(names("?a"), ["éa"]);
+    assert_eq!(names("*pane"), ["pane", "my pane"]);
+    assert_eq!(names("*_*{1}"), ["env_path"]);
+    assert_eq!(names(r"~/^pane(_\d+)?$/"), ["pane", "pane_1", "pane_12"]);
+    assert_eq!(names("~/ /"), ["my pane"]);
+    assert_eq!(names("~/^env/{1..}"), ["env_path", "envy"]);
+}
+
+#[test]
+fn query_string_matches() {
+    let kdoc: KdlDocument = r#"
+        plugin location="zellij:tab-bar" name=a
+        plugin location="file:/plugins/status.wasm" name=b
+        plugin location="https://example.com/helix.wasm" name=c
+        plugin location=1.2 name=d
+        pane "helix" "1.2.3" name=e
+        pane "yazi" "0.4" name=f
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, last_value);
+    assert_eq!(names(r#"plugin[location^="zellij:"]"#), ["a"]);
+    assert_eq!(names(r#"plugin[location$=".wasm"]"#), ["b", "c"]);
+    assert_eq!(names("plugin[location*=helix]"), ["c"]);
+    assert_eq!(names(r#"plugin[location~="^https://.*\.wasm$"]"#), ["c"]);
+    assert_eq!(names("plugin[location^=1]"), Vec::<String>::new());
+    assert_eq!(names("pane[0*=li 1^=1.2]"), ["e"]);
+    assert_eq!(names("pane[!0~=x]"), ["f"]);
+}
+
+#[test]
+fn query_projections() {
+    let matches = |query| {
+        Query::parse(query)
+            .unwrap()
+            .resolve_matches(&KDL_DOC)
+            .into_iter()
+            .map(|found| match found {
+                Match::Node(node) => format!("node {}", node.name().value()),
+                Match::Entry(entry) => format!("entry {}", entry.to_string().trim()),
+                Match::Value(value) => format!("value {value}"),
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        matches("node_prop/@hello"),
+        [
+            "entry hello=world",
+            "entry hello=world",
+            "entry hello=world"
+        ]
+    );
+    assert_eq!(matches("node_prop/$foo"), ["value bar"]);
+    assert_eq!(matches("node2/$1"), ["value 2"]);
+    assert_eq!(matches("node2/@*"), ["entry 1", "entry 2", "entry 3"]);
+    assert_eq!(matches("node_prop{1}/$*"), ["value world", "value 123"]);
+    assert_eq!(matches("node_children/*/$5"), Vec::<String>::new());
+    assert_eq!(
+        matches("node3{1}/$2 | node2{0} | node2{0}/@0 | node2/$0 | node3/$2"),
+        ["node node2", "entry 1", "value 1", "value c", "value 0"]
+    );
+    let names = |query| resolve_with(&KDL_DOC, query, text);
+    assert_eq!(names("node_prop/@foo"), ["node_prop hello=world foo=bar"]);
+    assert_eq!(names("node2/@*"), ["node2 1 2 3"]);
+    // The filter and the projection both use the last of the repeated properties
+    let kdoc: KdlDocument = "a x=1 x=2; b x=2 x=1".parse().unwrap();
+    let values = |query| {
+        Query::parse(query)
+            .unwrap()
+            .resolve_matches(&kdoc)
+            .into_iter()
+            .map(|found| match found {
+                Match::Value(value) => value.as_integer().unwrap(),
+                found => panic!("unexpected match {found:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(values("*[x=1]/$x"), [1]);
+    assert_eq!(values("*[x=2]/$x"), [2]);
+    assert_eq!(resolve_with(&kdoc, "*[x=1]", name), ["b"]);
+}
+
+#[test]
+fn query_ancestors() {
+    let found = |query| resolve_with(&KDL_DOC, query, name_and_first_value);
+    // The parent of a node having children is its actual parent
+    assert_eq!(found("article/contents/.."), ["article"]);
+    assert_eq!(found("article/contents{0}/section/.."), ["contents"]);
+    assert_eq!(found("article/contents/section/../.."), ["article"]);
+    assert_eq!(found("**/paragraph{3}/../.."), ["contents"]);
+    assert_eq!(
+        found(r#"**/section["Second section"]/../../contents{0}/section"#),
+        ["section \"First section\""]
+    );
+    assert_eq!(
+        found("**/paragraph/.."),
+        ["section \"First section\"", "section \"Second section\""]
+    );
+    assert_eq!(found("node_children/node1/../node3"), ["node3 3"]);
+    assert_eq!(found("node1/../node_children/node2"), ["node2 2"]);
+    // Above the top-level nodes is the document, which is not a node
+    assert_eq!(found("node1/.."), Vec::<String>::new());
+    assert_eq!(found("node1/../.."), Vec::<String>::new());
+    assert_eq!(found(".."), Vec::<String>::new());
+    // Every node having children, once
+    assert_eq!(found("**/..").len(), 7);
+    assert_eq!(found("**[1]/.."), ["node_children", "node_multiple"]);
+    assert_eq!(found("article/**/section/..[_]"), Vec::<String>::new());
+}
+
+#[test]
+fn query_axes() {
+    let kdoc: KdlDocument = r#"
+        layout {
+            pane name=yazi
+            pane name=helix
+            pane name=shell
+            tab name=tab {
+                pane name=inner
+            }
+        }
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| {
+        resolve_with(&kdoc, query, |node| {
+            node.entry("name").map_or_else(
+                || node.name().value().to_string(),
+                |e| e.value().to_string(),
+            )
+        })
+    };
+    assert_eq!(
+        names("**/pane[name=helix]/following-sibling::pane{0}"),
+        ["shell"]
+    );
+    assert_eq!(
+        names("**/pane[name=helix]/following-sibling::*"),
+        ["shell", "tab"]
+    );
+    assert_eq!(names("**/pane[name=helix]/preceding-sibling::*"), ["yazi"]);
+    // Reverse axes start from the nearest node
+    assert_eq!(
+        names("**/pane[name=shell]/preceding-sibling::pane{0}"),
+        ["helix"]
+    );
+    // The results are in document order, whatever the axis is
+    assert_eq!(names("**/pane[name=inner]/ancestor::*"), ["layout", "tab"]);
+    assert_eq!(names("**/pane[name=inner]/ancestor::*{1}"), ["layout"]);
+    assert_eq!(names("**/pane[name=inner]/ancestor::lay*"), ["layout"]);
+    assert_eq!(names("layout/ancestor::*"), Vec::<String>::new());
+    assert_eq!(
+        names("layout/tab/pane/following-sibling::*"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        names("**/tab/preceding-sibling::pane[name=yazi]/.."),
+        ["layout"]
+    );
+    assert_eq!(names("**/following-sibling::tab"), ["tab"]);
+}
+
+#[test]
+fn query_root_and_current() {
+    let names = |query| resolve_with(&KDL_DOC, query, name);
+    assert_eq!(names("/node_children/node3"), ["node3"]);
+    assert_eq!(names("node_children/node3/../../node1"), ["node1"]);
+    assert_eq!(names("node_children/node3/./../node1"), ["node1"]);
+    assert_eq!(names("node_children/node3/."), ["node3"]);
+    assert_eq!(names("./node1"), ["node1"]);
+    assert_eq!(names("."), Vec::<String>::new());
+    assert_eq!(names(".[0]"), Vec::<String>::new());
+    assert_eq!(names("node_multiple/*/.[4]"), ["node"]);
+    // The range applies to each current node on its own
+    assert_eq!(names("node_multiple/node/.{0}").len(), 5);
+    assert_eq!(names("node_multiple/node/.{1}"), Vec::<String>::new());
+    assert_eq!(names("article//paragraph"), names("article/**/paragraph"));
+    assert_eq!(names("//section"), ["section", "section"]);
+    // A leading slash goes back to the node the path is resolved from
+    let knode = KDL_DOC
+        .nodes()
+        .iter()
+        .find(|node| node.name().value() == "node_children")
+        .unwrap();
+    let found = Path::parse("/node_children/node2")
+        .unwrap()
+        .resolve_node_to_document(knode);
+    assert_eq!(found.nodes().len(), 1);
+    assert_eq!(found.nodes()[0].entries()[0].value().as_integer(), Some(2));
+}
+
+#[test]
+fn query_from_context() {
+    let names = |found: Vec<&KdlNode>| {
+        found
+            .into_iter()
+            .map(name_and_first_value)
+            .collect::<Vec<_>>()
+    };
+    let contents = Path::parse("article/contents").unwrap().resolve(&KDL_DOC);
+    let section = Path::parse("section").unwrap();
+    assert_eq!(
+        names(section.clone().resolve_from(contents[1])),
+        ["section \"Second section\""]
+    );
+    assert_eq!(
+        names(section.clone().resolve_in(&KDL_DOC, &contents)),
+        ["section \"First section\"", "section \"Second section\""]
+    );
+    // The ancestors are only known when the context is found in the document
+    let parents = Path::parse("../../node1").unwrap();
+    assert_eq!(
+        names(parents.clone().resolve_from(contents[0])),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        names(parents.clone().resolve_in(&KDL_DOC, &contents[..1])),
+        ["node1"]
+    );
+    let root = Path::parse("/node_children/node2").unwrap();
+    assert_eq!(
+        names(root.clone().resolve_in(&KDL_DOC, &contents[..1])),
+        ["node2 2"]
+    );
+    assert_eq!(
+        names(Path::parse("/contents").unwrap().resolve_from(contents[0])),
+        ["contents"]
+    );
+    assert_eq!(
+        names(
+            Path::parse("following-sibling::*")
+                .unwrap()
+                .resolve_in(&KDL_DOC, &contents[..1])
+        ),
+        ["contents"]
+    );
+    assert_eq!(
+        names(
+            Path::parse("following-sibling::*")
+                .unwrap()
+                .resolve_from(contents[0])
+        ),
+        Vec::<String>::new()
+    );
+    // A node outside of the document has no known ancestors
+    let kdoc: KdlDocument = "outside { inner }".parse().unwrap();
+    let outside = &kdoc.nodes()[0];
+    assert_eq!(
+        names(
+            Path::parse("inner/..")
+                .unwrap()
+                .resolve_in(&KDL_DOC, &[outside])
+        ),
+        ["outside"]
+    );
+    assert_eq!(
+        names(Path::parse("..").unwrap().resolve_in(&KDL_DOC, &[outside])),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        names(
+            Path::parse("/node1")
+                .unwrap()
+                .resolve_in(&KDL_DOC, &[outside])
+        ),
+        ["node1"]
+    );
+    // A single node is the only top-level node of the document, with either result
+    let kdoc: KdlDocument = "parent { first; second; }".parse().unwrap();
+    let parent = &kdoc.nodes()[0];
+    for (query, expected) in [
+        ("/parent", vec!["parent"]),
+        ("/parent/second", vec!["second"]),
+        ("/first", vec![]),
+        ("first/..", vec!["parent"]),
+        ("first/../..", vec![]),
+        ("first/../second", vec!["second"]),
+    ] {
+        let path = Path::parse(query).unwrap();
+        assert_eq!(names(path.clone().resolve_from(parent)), expected);
+        let found = path.resolve_node_to_document(parent);
+        assert_eq!(names(found.nodes().iter().collect()), expected);
+        let union = Query::parse(query).unwrap();
+        assert_eq!(names(union.clone().resolve_from(parent)), expected);
+        let found = union.resolve_node_to_document(parent);
+        assert_eq!(names(found.nodes().iter().collect()), expected);
+    }
+}
+
+#[test]
+fn query_lazy() {
+    for query in [
+        "node2",
+        "**/paragraph",
+        "article/**/section/..",
+        "**/paragraph{1..3}",
+        "node_multiple/node[2]/following-sibling::*",
+        "**/$0",
+    ] {
+        let path = Path::parse(query).unwrap();
+        let iterated = path.iter(&KDL_DOC).collect::<Vec<_>>();
+        assert_eq!(iterated, path.clone().resolve(&KDL_DOC), "{query}");
+        assert_eq!(path.count(&KDL_DOC), iterated.len(), "{query}");
+        assert_eq!(path.first(&KDL_DOC), iterated.first().copied(), "{query}");
+        assert!(path.exists(&KDL_DOC), "{query}");
+    }
+    let path = Path::parse("**/missing").unwrap();
+    assert_eq!(path.first(&KDL_DOC), None);
+    assert!(!path.exists(&KDL_DOC));
+    assert_eq!(path.count(&KDL_DOC), 0);
+    let path = Path::parse("node_multiple/*").unwrap();
+    let mut it = path.iter(&KDL_DOC);
+    assert_eq!(
+        it.next().unwrap().entries()[0].value().as_integer(),
+        Some(1)
+    );
+    assert_eq!(
+        it.next().unwrap().entries()[0].value().as_integer(),
+        Some(2)
+    );
+}
+
+#[test]
+fn query_orders() {
+    let kdoc: KdlDocument = r#"
+        a {
+            b {
+                c
+                d
+            }
+            e {
+                f
+            }
+        }
+        g
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("**"), ["a", "b", "c", "d", "e", "f", "g"]);
+    assert_eq!(names("**<pre>"), ["a", "b", "c", "d", "e", "f", "g"]);
+    assert_eq!(names("**<post>"), ["c", "d", "b", "f", "e", "a", "g"]);
+    assert_eq!(names("**<breadth>"), ["a", "g", "b", "e", "c", "d", "f"]);
+    assert_eq!(names("**<tree>"), ["a", "g", "b", "e", "c", "d", "f"]);
+    assert_eq!(names("a/**<tree>"), ["b", "e", "c", "d", "f"]);
+    assert_eq!(names("a/**<post>/*"), ["c", "d", "b", "f", "e"]);
+    assert_eq!(names("**<post>/*{0}"), ["c"]);
+    assert_eq!(names("**<breadth>/*{..1}"), ["a", "g"]);
+    assert_eq!(names("**<post>/.."), ["b", "a", "e"]);
+    let order = |order| {
+        Path::parse("a/**")
+            .unwrap()
+            .with_order(order)
+            .resolve(&kdoc)
+            .into_iter()
+            .map(|node| node.name().value())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(order(Order::PostOrder), ["c", "d", "b", "f", "e"]);
+    assert_eq!(order(Order::FileTree), ["b", "e", "c", "d", "f"]);
+    // The file tree order lists the children of a node before going deeper
+    let kdoc: KdlDocument = "a { b { c; }; d; }; e { f; }".parse().unwrap();
+    let tree = Path::parse("**<tree>")
+        .unwrap()
+        .resolve(&kdoc)
+        .into_iter()
+        .map(|node| node.name().value())
+        .collect::<Vec<_>>();
+    assert_eq!(tree, ["a", "e", "b", "d", "c", "f"]);
+    let breadth = Path::parse("**<breadth>")
+        .unwrap()
+        .resolve(&kdoc)
+        .into_iter()
+        .map(|node| node.name().value())
+        .collect::<Vec<_>>();
+    assert_eq!(breadth, ["a", "e", "b", "d", "f", "c"]);
+}
+
+#[test]
+fn query_depths() {
+    let kdoc: KdlDocument = r#"
+        a {
+            b {
+                c {
+                    d
+                }
+            }
+            e
+        }
+        f
+        "#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("**<1>"), ["a", "f"]);
+    assert_eq!(names("**<2..3>"), ["b", "c", "e"]);
+    assert_eq!(names("**<..2>"), ["a", "b", "e", "f"]);
+    assert_eq!(names("**<3..>"), ["c", "d"]);
+    assert_eq!(names("a/**<1..2>/*"), ["b", "c", "e"]);
+    assert_eq!(names("a/**<2>"), ["c"]);
+    assert_eq!(names("**<0>"), Vec::<&str>::new());
+    assert_eq!(names("**<..<2>"), ["a", "f"]);
+    assert_eq!(names("**<2..<3>"), ["b", "e"]);
+    assert_eq!(names("**<post ..2>"), ["b", "e", "a", "f"]);
+    assert_eq!(names("**<breadth 2..3>"), ["b", "e", "c"]);
+    assert_eq!(names("**<tree 2..3>"), ["b", "e", "c"]);
+}
+
+#[test]
+fn query_duplicates() {
+    let kdoc: KdlDocument = r#"
+        pane 1 {
+            pane 2 {
+                pane 3
+            }
+        }
+        "#
+    .parse()
+    .unwrap();
+    let values = |found: Vec<&KdlNode>| {
+        found
+            .into_iter()
+            .map(|node| node.entries()[0].value().as_integer().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let path = Path::parse("**/**/pane").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [2, 3]);
+    assert_eq!(
+        values(path.clone().resolve_with_duplicates(&kdoc)),
+        [2, 3, 3]
+    );
+    assert_eq!(values(path.iter(&kdoc).collect()), [2, 3]);
+    assert_eq!(path.count(&kdoc), 2);
+    let path = Path::parse("**/pane/**/pane").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [2, 3]);
+    assert_eq!(values(path.resolve_with_duplicates(&kdoc)), [2, 3, 3]);
+    // Going up finds the nodes out of document order, they are still iterated in it
+    let path = Path::parse("**/pane[3]/ancestor::*").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [1, 2]);
+    assert_eq!(values(path.iter(&kdoc).collect()), [1, 2]);
+    assert_eq!(values(path.first(&kdoc).into_iter().collect()), [1]);
+    let path = Path::parse("**/pane/..").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [1, 2]);
+    assert_eq!(values(path.resolve_with_duplicates(&kdoc)), [1, 2]);
+    // The union of paths is a set too
+    let query = Query::parse("**/pane[2] | **/**/pane").unwrap();
+    assert_eq!(values(query.clone().resolve(&kdoc)), [2, 3]);
+    assert_eq!(values(query.iter(&kdoc).collect()), [2, 3]);
+    assert_eq!(values(query.first(&kdoc).into_iter().collect()), [2]);
+    assert_eq!(query.count(&kdoc), 2);
+    assert!(query.exists(&kdoc));
+    assert!(!Query::parse("pane[2] | **/node").unwrap().exists(&kdoc));
+    let query = Query::parse("**/**/pane").unwrap();
+    assert_eq!(values(query.clone().resolve(&kdoc)), [2, 3]);
+    assert_eq!(values(query.iter(&kdoc).collect()), [2, 3]);
+    // The steps after `**` find the nodes of a nested subtree first
+    let kdoc: KdlDocument = "a 1 { a 2 { b 3; }; b 4; }".parse().unwrap();
+    let path = Path::parse("**/a/b").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [3, 4]);
+    assert_eq!(values(path.iter(&kdoc).collect()), [3, 4]);
+    assert_eq!(values(path.first(&kdoc).into_iter().collect()), [3]);
+    let path = Path::parse("**/a/*").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [2, 3, 4]);
+    assert_eq!(values(path.iter(&kdoc).collect()), [2, 3, 4]);
+    let path = Path::parse("**[1]/*").unwrap();
+    assert_eq!(values(path.clone().resolve(&kdoc)), [2, 4]);
+}
+
+#[test]
+fn query_ranges_from_end() {
+    let values = |query| resolve_with(&KDL_DOC, query, first_integer);
+    assert_eq!(values("node_multiple/node{-1}"), [5]);
+    assert_eq!(values("node_multiple/node{last}"), [5]);
+    assert_eq!(values("node_multiple/node{-5}"), [1]);
+    assert_eq!(values("node_multiple/node{-6}"), Vec::<i128>::new());
+    assert_eq!(values("node_multiple/node{..-2}"), [1, 2, 3, 4]);
+    assert_eq!(values("node_multiple/node{..<-2}"), [1, 2, 3]);
+    assert_eq!(values("node_multiple/node{-3..}"), [3, 4, 5]);
+    assert_eq!(values("node_multiple/node{-10..}"), [1, 2, 3, 4, 5]);
+    assert_eq!(values("node_multiple/node{1..-1}"), [2, 3, 4, 5]);
+    assert_eq!(values("node_multiple/node{1..<-1}"), [2, 3, 4]);
+    assert_eq!(values("node_multiple/node{-4..3}"), [2, 3, 4]);
+    assert_eq!(values("node_multiple/node{-1..-3}"), Vec::<i128>::new());
+    // The bounds may be far apart without overflowing
+    assert_eq!(
+        values("node_multiple/node{170141183460469231731687303715884105727..-170141183460469231731687303715884105727}"),
+        Vec::<i128>::new()
+    );
+    assert_eq!(
+        values("node_multiple/node{170141183460469231731687303715884105727..<-170141183460469231731687303715884105727}"),
+        Vec::<i128>::new()
+    );
+    assert_eq!(
+        values("node_multiple/node{-170141183460469231731687303715884105727..170141183460469231731687303715884105727}"),
+        [1, 2, 3, 4, 5]
+    );
+    assert_eq!(
+        values("node_multiple/node{-170141183460469231731687303715884105727..<170141183460469231731687303715884105727}"),
+        [1, 2, 3, 4, 5]
+    );
+    // The length is the one of the nodes matching the name and the entries
+    assert_eq!(values("node_multiple/*[0<4]{-1}"), [3]);
+    assert_eq!(values("node_children/*[0<3]{last}"), [2]);
+    assert_eq!(values("**/node2{last}"), [2]);
+    assert_eq!(
+        values("node_multiple/node{0}/following-sibling::*{-2}"),
+        [4]
+    );
+}
+
+#[test]
+fn query_range_ends_and_steps() {
+    let values = |query| resolve_with(&KDL_DOC, query, first_integer);
+    assert_eq!(values("node_multiple/node{..1}"), [1, 2]);
+    assert_eq!(values("node_multiple/node{..=1}"), [1, 2]);
+    assert_eq!(values("node_multiple/node{..<1}"), [1]);
+    assert_eq!(values("node_multiple/node{1..=2}"), [2, 3]);
+    assert_eq!(values("node_multiple/node{1..<2}"), [2]);
+    assert_eq!(values("node_multiple/node{2..<2}"), Vec::<i128>::new());
+    assert_eq!(values("node_multiple/node{3..1}"), Vec::<i128>::new());
+    assert_eq!(values("node_multiple/node{..;2}"), [1, 3, 5]);
+    assert_eq!(values("node_multiple/node{0..10;2}"), [1, 3, 5]);
+    assert_eq!(values("node_multiple/node{1..;2}"), [2, 4]);
+    assert_eq!(values("node_multiple/node{..<4;3}"), [1, 4]);
+    assert_eq!(values("node_multiple/node{-2..;1}"), [4, 5]);
+    assert_eq!(values("**/node{..;4}"), [1, 5]);
+    assert_eq!(values("node_children/*{1..2;2}"), [2]);
+}
+
+#[test]
+fn query_kdl_identifiers() {
+    let kdoc: KdlDocument = r#"
+layout {
+    zellij:tab-bar
+    pane split-direction=vertical {
+        pane size=-1 env-vars="a-b"
+        pane size=+3.5
+    }
+}
+"#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("layout/zellij:tab-bar"), ["zellij:tab-bar"]);
+    assert_eq!(names("**/pane[split-direction=vertical]"), ["pane"]);
+    assert_eq!(names("**/pane[env-vars=a-b]"), ["pane"]);
+    assert_eq!(names("**/pane[size=-1]"), ["pane"]);
+    assert_eq!(names("**/pane[size>=+3.5]"), ["pane"]);
+    assert_eq!(names("**/pane[size<-1]"), Vec::<&str>::new());
+}
+
+#[test]
+fn query_quoted_strings() {
+    let kdoc: KdlDocument = r###"
+"quoted name" path=#"C:\Program Files\"#
+"it's" text="""
+    two
+    lines
+    """
+node version="1.2.3"
+"###
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names(r#""quoted name""#), ["quoted name"]);
+    assert_eq!(names("'quoted name'"), ["quoted name"]);
+    assert_eq!(names(r##"*[path=#"C:\Program Files\"#]"##), ["quoted name"]);
+    assert_eq!(names(r#"*[path="C:\\Program Files\\"]"#), ["quoted name"]);
+    assert_eq!(names(r##"*[path^=#"C:\"#]"##), ["quoted name"]);
+    assert_eq!(names(r#"'it\'s'[text="two\nlines"]"#), ["it's"]);
+    assert_eq!(names("*[text=\"\"\"\n  two\n  lines\n  \"\"\"]"), ["it's"]);
+    assert_eq!(names(r##"*[version~=#"^1\.\d"#]"##), ["node"]);
+    assert_eq!(names(r##"*[version~=#"^1\d"#]"##), Vec::<&str>::new());
+}
+
+#[test]
+fn query_special_floats() {
+    let kdoc: KdlDocument = "a #inf; b #-inf; c #nan; d 1.5; e \"inf\"".parse().unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("*[0=#inf]"), ["a"]);
+    assert_eq!(names("*[0=#-inf]"), ["b"]);
+    assert_eq!(names("*[0=#nan]"), ["c"]);
+    assert_eq!(names("*[0>1]"), ["a", "d"]);
+    assert_eq!(names("*[0<1]"), ["b"]);
+    assert_eq!(names("*[0!=#nan]"), ["a", "b", "d", "e"]);
+    // NaN can't be ordered, but is equal to itself
+    assert_eq!(names("*[0>#nan]"), Vec::<&str>::new());
+    assert_eq!(names("*[0>=#nan]"), ["c"]);
+    assert_eq!(names("*[0=inf]"), ["e"]);
+}
+
+#[test]
+fn query_annotations() {
+    let kdoc: KdlDocument = r#"
+(widget)button size=(u8)12 (date)"2024-01-01" {
+    label "ok"
+}
+(widget)label
+button size=12 "2024-01-01"
+"#
+    .parse()
+    .unwrap();
+    let names = |query| resolve_with(&kdoc, query, name);
+    assert_eq!(names("(widget)*"), ["button", "label"]);
+    assert_eq!(names("(widget)button"), ["button"]);
+    assert_eq!(names("(other)*"), Vec::<&str>::new());
+    assert_eq!(names("*[size=(u8)_]").len(), 1);
+    assert_eq!(names("*[size=(u8)12]").len(), 1);
+    assert_eq!(names("*[size=12]").len(), 2);
+    assert_eq!(names("*[(date)0=_]").len(), 1);
+    assert_eq!(names("*[(date)_]").len(), 1);
+    assert_eq!(names("*[!(date)_]"), ["label", "button"]);
+    assert_eq!(names("**/label/ancestor::(widget)*"), ["button"]);
+    assert_eq!(names("**/label/..[size=(u8)_]"), ["button"]);
+    // The annotation of `**` applies to the descendants, not to the next step
+    assert_eq!(names("(widget)**/label"), ["label"]);
+}
+
+#[test]
+fn query_typed_comparisons() {
+    let kdoc: KdlDocument = r#"
+deploy name=a expires=(date)"2026-03-01" version=(semver)"1.4.2" timeout=(duration)"30s"
+deploy name=b expires=(date)"2026-12-31" version=(semver)"1.10.0" timeout=(duration)"2m"
+deploy name=c expires=(date)"2027-01-15" version=(semver)"1.10.0-rc.1" timeout=(duration)"1h 5m"
+deploy name=d expires="2026-2-30" version="v2" timeout=(duration)"PT45S"
+"#
+    .parse()
+    .unwrap();
+    let names = |query| {
+        resolve_with(&kdoc, query, |node| {
+            node.entries()[0].value().as_string().unwrap()
+        })
+    };
+    assert_eq!(names(r#"*[expires<(date)"2026-12-31"]"#), ["a"]);
+    assert_eq!(names(r#"*[expires<=(date)"2026-12-31"]"#), ["a", "b"]);
+    assert_eq!(names(r#"*[expires>(date)"2026-2-1"]"#), ["a", "b", "c"]);
+    // Without an annotation in the query, the one of the entry gives the type, the text of `d`
+    // is compared as it has none
+    assert_eq!(names(r#"*[expires>"2026-06-01"]"#), ["b", "c", "d"]);
+    // Invalid dates are never ordered
+    assert_eq!(
+        names(r#"*[expires>(date)"2026-02-30"]"#),
+        Vec::<&str>::new()
+    );
+
+    assert_eq!(names(r#"*[version>(semver)"1.9.0"]"#), ["b", "c"]);
+    assert_eq!(names(r#"*[version<(semver)"1.10.0"]"#), ["a", "c"]);
+    assert_eq!(names(r#"*[version=(semver)"1.10.0+build.5"]"#), ["b"]);
+    assert_eq!(names(r#"*[version<"1.10.0-rc.2"]"#), ["a", "c"]);
+    assert_eq!(names(r#"*[version<"1.10.0-rc.10"]"#), ["a", "c"]);
+    assert_eq!(names(r#"*[version<"1.10.0-beta"]"#), ["a"]);
+
+    assert_eq!(names(r#"*[timeout<(duration)"90s"]"#), ["a", "d"]);
+    assert_eq!(names(r#"*[timeout>=(duration)"1.5m"]"#), ["b", "c"]);
+    assert_eq!(names(r#"*[timeout=(duration)PT1H5M]"#), ["c"]);
+    assert_eq!(names(r#"*[timeout=(duration)"45s"]"#), ["d"]);
+    assert_eq!(names(r#"*[timeout>(duration)P1M]"#), Vec::<&str>::new());
+}
+
+#[test]
+fn query_custom_value_types() {
+    use crate::resolve::{SemVer, ValueType, ValueTypes};
+    use std::cmp::Ordering;
+    /// Orders texts by their length
+    struct Length;
+    impl ValueType for Length {
+        fn compare(&self, left: &str, right: &str) -> Option<Ordering> {
+            Some(left.len().cmp(&right.len()))
+        }
+    }
+    let mut value_types = ValueTypes::default();
+    value_types.insert("length", Length);
+    value_types.insert("version", SemVer);
+    let kdoc: KdlDocument = r#"
+a (length)"abc" (version)"0.9.0" (date)"2026-03-01"
+b (length)"z" (version)"0.10.0" (date)"2026-12-31"
+c (unknown)"abc" (unknown)"0.9.0" (date)"2026-02-30"
+"#
+    .parse()
+    .unwrap();
+    let names = |query, value_types: &ValueTypes| {
+        Path::parse(query)
+            .unwrap()
+            .with_types(value_types)
+            .resolve(&kdoc)
+            .into_iter()
+            .map(|node| node.name().value())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(r#"*[0>(length)"xy"]"#, &value_types), ["a"]);
+    assert_eq!(names(r#"*[0=(length)"xyz"]"#, &value_types), ["a"]);
+    assert_eq!(names(r#"*[1>"0.9.1"]"#, &value_types), ["b"]);
+    // The default types are kept
+    assert_eq!(names(r#"*[2<(date)"2026-06-01"]"#, &value_types), ["a"]);
+    // Texts of an unregistered type are compared lexicographically
+    assert_eq!(
+        names(r#"*[0>(unknown)"b"]"#, &value_types),
+        Vec::<&str>::new()
+    );
+    assert_eq!(names(r#"*[0<(unknown)"b"]"#, &value_types), ["c"]);
+    assert_eq!(names(r#"*[1>"0.10.0"]"#, &value_types), ["c"]);
+    // Types only apply to the resolution they are given to
+    assert_eq!(names(r#"*[1<"0.9.1"]"#, &value_types), ["a", "c"]);
+    assert_eq!(
+        names(r#"*[1<"0.9.1"]"#, &ValueTypes::default()),
+        ["a", "b", "c"]
+    );
+    assert_eq!(
+        names(r#"*[2<"2026-06-01"]"#, &ValueTypes::empty()),
+        ["a", "c"]
+    );
+    let query = Query::parse(r#"*[0>(length)"xy"] | *[1>"0.9.1"]"#).unwrap();
+    assert_eq!(
+        query.with_types(&value_types).resolve(&kdoc),
+        [&kdoc.nodes()[0], &kdoc.nodes()[1]]
+    );
+    assert_eq!(query.with_types(&value_types).count(&kdoc), 2);
+    assert_eq!(query.count(&kdoc), 1);
+    // Every way of resolving the path uses the types
+    let path = Path::parse(r#"*[1>"0.9.1"]"#).unwrap();
+    let typed = path.with_types(&value_types);
+    assert_eq!(typed.iter(&kdoc).map(name).collect::<Vec<_>>(), ["b"]);
+    assert_eq!(typed.first(&kdoc).map(name), Some("b"));
+    assert!(typed.exists(&kdoc));
+    assert_eq!(typed.count(&kdoc), 1);
+    assert_eq!(typed.resolve_with_duplicates(&kdoc).len(), 1);
+    assert_eq!(typed.resolve_matches(&kdoc).len(), 1);
+    assert_eq!(typed.resolve_in(&kdoc, &[]), Vec::<&KdlNode>::new());
+    assert_eq!(typed.resolve_to_document(&kdoc).nodes().len(), 1);
+    let root: KdlNode = r#"root { a (version)"0.9.0"; b (version)"0.10.0"; }"#
+        .parse()
+        .unwrap();
+    let from_root = Path::parse(r#"*[0>"0.9.1"]"#).unwrap();
+    let typed = from_root.with_types(&value_types);
+    assert_eq!(
+        typed
+            .resolve_from(&root)
+            .into_iter()
+            .map(name)
+            .collect::<Vec<_>>(),
+        ["b"]
+    );
+    assert_eq!(typed.resolve_node_to_document(&root).nodes().len(), 1);
+    assert!(!path.exists(&kdoc));
+}
@@ -0,0 +1,138 @@
+use super::{Match, Resolver, Scope, ValueTypes};
+use crate::parser::{Path, Query};
+use kdl::{KdlDocument, KdlNode};
+
+/// Path or query resolved with given value types, returned by [`Path::with_types`] and
+/// [`Query::with_types`]. Its methods are the ones of the path or query, the annotated texts
+/// being compared with the value types in every one of them
+#[derive(Debug)]
+pub struct WithTypes<'q, 't, Q> {
+    query: &'q Q,
+    value_types: &'t ValueTypes,
+}
+
+impl<Q> Clone for WithTypes<'_, '_, Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Q> Copy for WithTypes<'_, '_, Q> {}
+
+impl<'q, 't, Q> WithTypes<'q, 't, Q> {
+    pub(crate) fn new(query: &'q Q, value_types: &'t ValueTypes) -> Self {
+        Self { query, value_types }
+    }
+    fn scope<'k>(&self, scope: Scope<'k, 'static>) -> Scope<'k, 't> {
+        scope.with_value_types(self.value_types)
+    }
+}
+
+impl<'q, 't, 'a> WithTypes<'q, 't, Path<'a>> {
+    /// Same as [`Path::resolve`]
+    pub fn resolve(self, kdoc: &KdlDocument) -> Vec<&KdlNode> {
+        Resolver::resolve_nodes(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Path::resolve_with_duplicates`]
+    pub fn resolve_with_duplicates(self, kdoc: &KdlDocument) -> Vec<&KdlNode> {
+        Resolver::resolve_nodes_with_duplicates(
+            &self.scope(Scope::document(kdoc.nodes())),
+            self.query,
+        )
+    }
+    /// Same as [`Path::resolve_matches`]
+    pub fn resolve_matches(self, kdoc: &KdlDocument) -> Vec<Match<'_>> {
+        Resolver::resolve_matches(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Path::iter`]
+    pub fn iter<'k>(self, kdoc: &'k KdlDocument) -> impl Iterator<Item = &'k KdlNode> + 'k
+    where
+        'q: 'k,
+        't: 'k,
+    {
+        Resolver::iter(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Path::first`]
+    pub fn first(self, kdoc: &KdlDocument) -> Option<&KdlNode> {
+        Resolver::iter(&self.scope(Scope::document(kdoc.nodes())), self.query).next()
+    }
+    /// Same as [`Path::exists`]
+    pub fn exists(self, kdoc: &KdlDocument) -> bool {
+        self.first(kdoc).is_some()
+    }
+    /// Same as [`Path::count`]
+    pub fn count(self, kdoc: &KdlDocument) -> usize {
+        Resolver::iter(&self.scope(Scope::document(kdoc.nodes())), self.query).count()
+    }
+    /// Same as [`Path::resolve_from`]
+    pub fn resolve_from(self, knode: &KdlNode) -> Vec<&KdlNode> {
+        Resolver::resolve_nodes(&self.scope(Scope::node(knode)), self.query)
+    }
+    /// Same as [`Path::resolve_in`]
+    pub fn resolve_in<'k>(
+        self,
+        kdoc: &'k KdlDocument,
+        contexts: &[&'k KdlNode],
+    ) -> Vec<&'k KdlNode> {
+        Resolver::resolve_nodes(&self.scope(Scope::nodes_in(kdoc, contexts)), self.query)
+    }
+    /// Same as [`Path::resolve_to_document`]
+    pub fn resolve_to_document(self, kdoc: &KdlDocument) -> KdlDocument {
+        super::to_document(self.resolve(kdoc))
+    }
+    /// Same as [`Path::resolve_node_to_document`]
+    pub fn resolve_node_to_document(self, knode: &KdlNode) -> KdlDocument {
+        super::to_document(self.resolve_from(knode))
+    }
+}
+
+impl<'q, 't, 'a> WithTypes<'q, 't, Query<'a>> {
+    /// Same as [`Query::resolve`]
+    pub fn resolve(self, kdoc: &KdlDocument) -> Vec<&KdlNode> {
+        Resolver::resolve_union(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Query::resolve_matches`]
+    pub fn resolve_matches(self, kdoc: &KdlDocument) -> Vec<Match<'_>> {
+        Resolver::resolve_union_matches(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Query::iter`]
+    pub fn iter<'k>(self, kdoc: &'k KdlDocument) -> impl Iterator<Item = &'k KdlNode> + 'k
+    where
+        'q: 'k,
+        't: 'k,
+    {
+        Resolver::iter_union(&self.scope(Scope::document(kdoc.nodes())), self.query)
+    }
+    /// Same as [`Query::first`]
+    pub fn first(self, kdoc: &KdlDocument) -> Option<&KdlNode> {
+        Resolver::iter_union(&self.scope(Scope::document(kdoc.nodes())), self.query).next()
+    }
+    /// Same as [`Query::exists`]
+    pub fn exists(self, kdoc: &KdlDocument) -> bool {
+        self.first(kdoc).is_some()
+    }
+    /// Same as [`Query::count`]
+    pub fn count(self, kdoc: &KdlDocument) -> usize {
+        Resolver::iter_union(&self.scope(Scope::document(kdoc.nodes())), self.query).count()
+    }
+    /// Same as [`Query::resolve_from`]
+    pub fn resolve_from(self, knode: &KdlNode) -> Vec<&KdlNode> {
+        Resolver::resolve_union(&self.scope(Scope::node(knode)), self.query)
+    }
+    /// Same as [`Query::resolve_in`]
+    pub fn resolve_in<'k>(
+        self,
+        kdoc: &'k KdlDocument,
+        contexts: &[&'k KdlNode],
+    ) -> Vec<&'k KdlNode> {
+        Resolver::resolve_union(&self.scope(Scope::nodes_in(kdoc, contexts)), self.query)
+    }
+    /// Same as [`Query::resolve_to_document`]
+    pub fn resolve_to_document(self, kdoc: &KdlDocument) -> KdlDocument {
+        super::to_document(self.resolve(kdoc))
+    }
+    /// Same as [`Query::resolve_node_to_document`]
+    pub fn resolve_node_to_document(self, knode: &KdlNode) -> KdlDocument {
+        super::to_document(self.resolve_from(knode))
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(unused_macros)]
 macro_rules! hashmap {
     ($($k:expr => $v:expr$(,)?)*) => {{
         let mut hashmap = std::collections::hash_map::HashMap::new();
@@ -7,4 +8,5 @@ macro_rules! hashmap {
         hashmap
     }};
 }
+#[allow(unused_imports)]
 pub(crate) use hashmap;